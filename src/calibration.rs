@@ -0,0 +1,68 @@
+use crate::display::{Edge, GridPlacement, Layout};
+
+/// Collects where each numbered peer sits relative to this machine
+pub struct Calibration {
+    /// The name or ip of every peer. Peers are numbered from 1 in this order.
+    peers: Vec<String>,
+    sides: Vec<Option<Edge>>,
+}
+
+impl Calibration {
+    pub fn new(peers: Vec<String>) -> Self {
+        let sides = vec![None; peers.len()];
+        Calibration { peers, sides }
+    }
+
+    /// Records that the peer with `number` sits at `edge` of this machine. Returns its name.
+    pub fn record(&mut self, number: usize, edge: Edge) -> Option<&String> {
+        if number == 0 || number > self.peers.len() {
+            return None;
+        }
+        self.sides[number - 1] = Some(edge);
+        self.peers.get(number - 1)
+    }
+
+    /// Places this machine in the first cell and every recorded peer next to it. Peers at the same
+    /// side are lined up outwards in the order of their numbers. Everything but the grid is kept
+    /// from `base`.
+    pub fn layout(&self, own: String, base: &Layout) -> Layout {
+        let mut grid = vec![GridPlacement {
+            client: own,
+            column: 0,
+            row: 0,
+        }];
+        let (mut left, mut right, mut top, mut bottom) = (0, 0, 0, 0);
+        for (peer, side) in self.peers.iter().zip(self.sides.iter()) {
+            let (column, row) = match side {
+                Some(Edge::Left) => {
+                    left += 1;
+                    (-left, 0)
+                }
+                Some(Edge::Right) => {
+                    right += 1;
+                    (right, 0)
+                }
+                Some(Edge::Top) => {
+                    top += 1;
+                    (0, -top)
+                }
+                Some(Edge::Bottom) => {
+                    bottom += 1;
+                    (0, bottom)
+                }
+                None => continue,
+            };
+            grid.push(GridPlacement {
+                client: peer.clone(),
+                column,
+                row,
+            });
+        }
+
+        Layout {
+            version: base.version + 1,
+            grid,
+            ..base.clone()
+        }
+    }
+}
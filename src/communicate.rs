@@ -1,10 +1,10 @@
 use {
-    crate::display::DisplayManager,
+    crate::{display::DisplayManager, file_transfer::KnownPeers},
     async_trait::async_trait,
     std::{
         error::Error,
         fmt,
-        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        net::{self, Ipv4Addr, SocketAddr, SocketAddrV4},
         sync::Arc,
         time::{Duration, Instant},
     },
@@ -14,6 +14,9 @@ use {
 
 type Result<T> = std::result::Result<T, CommunicateError>;
 
+/// The largest udp payload, a whole layout has to fit into one message
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
 #[derive(Debug)]
 pub enum CommunicateError {
     SocketCreationError(std::io::Error),
@@ -45,13 +48,16 @@ impl From<std::io::Error> for CommunicateError {
 pub struct ReceiverDevice {
     pub updated: Instant,
     pub socket_addr: SocketAddrV4,
+    /// The address of this machine as the device sees it
+    pub local_addr: Option<SocketAddrV4>,
 }
 
 impl ReceiverDevice {
-    pub fn new(addr: SocketAddrV4) -> Self {
+    pub fn new(addr: SocketAddrV4, local_port: u16) -> Self {
         ReceiverDevice {
             updated: Instant::now(),
             socket_addr: addr,
+            local_addr: route_local_addr(addr, local_port),
         }
     }
 
@@ -84,6 +90,17 @@ pub struct Communicate {
     updates: Arc<Mutex<Option<Box<dyn ClientUpdates>>>>,
 }
 
+#[async_trait]
+impl KnownPeers for Communicate {
+    async fn is_peer(&self, ip: &Ipv4Addr) -> bool {
+        self.devices
+            .lock()
+            .await
+            .iter()
+            .any(|v| v.socket_addr.ip() == ip)
+    }
+}
+
 impl Communicate {
     pub async fn new(
         multicast_addr: SocketAddrV4,
@@ -123,6 +140,20 @@ impl Communicate {
         self.self_addr.lock().await.clone()
     }
 
+    /// Whether `ip` belongs to this machine. Every address used to reach a peer counts.
+    pub async fn is_own_ip(&self, ip: &Ipv4Addr) -> bool {
+        if let Some(v) = &*self.self_addr.lock().await {
+            if v.ip() == ip {
+                return true;
+            }
+        }
+        self.devices
+            .lock()
+            .await
+            .iter()
+            .any(|v| matches!(v.local_addr, Some(addr) if addr.ip() == ip))
+    }
+
     pub async fn send(&self, message: String) -> Result<()> {
         for client in self.devices.lock().await.iter() {
             self.main_socket
@@ -155,7 +186,7 @@ impl Communicate {
     }
 
     pub async fn receive(&self, callback: impl Fn(&str, SocketAddr)) {
-        let mut buf: [u8; 2024] = [0; 2024];
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
         loop {
             match self.main_socket.recv_from(&mut buf).await {
                 Ok((amount, socket_addr)) => {
@@ -197,9 +228,16 @@ impl Communicate {
         let self_addr = self.self_addr.clone();
         let display_manager = self.display_manager.clone();
         let updates = self.updates.clone();
+        let local_port = match self.main_socket.local_addr() {
+            Ok(v) => v.port(),
+            Err(e) => {
+                println!("Unable to read own port: {}", e);
+                0
+            }
+        };
 
         tokio::spawn(async move {
-            let mut buf: [u8; 2024] = [0; 2024];
+            let mut buf = vec![0; MAX_DATAGRAM_SIZE];
             loop {
                 match global_socket.recv_from(&mut buf).await {
                     Ok((amount, src)) => {
@@ -218,15 +256,9 @@ impl Communicate {
                                 continue;
                             }
                         };
+                        //the echo of our own beacon is not reliable (multicast loop can be off or
+                        //it arrives through another interface), the own address is routed instead
                         if uuid == self_id {
-                            let mut lock = self_addr.lock().await;
-                            match src {
-                                SocketAddr::V4(src) => {
-                                    display_manager.lock().await.set_own_ip(src.clone());
-                                    *lock = Some(src)
-                                }
-                                _ => println!("Received self_address as V6! Invalid!"),
-                            }
                             continue;
                         }
                         let mut devices = devices.lock().await;
@@ -243,7 +275,21 @@ impl Communicate {
 
                         match (found, src) {
                             (false, SocketAddr::V4(v)) => {
-                                devices.push(ReceiverDevice::new(v));
+                                let device = ReceiverDevice::new(v, local_port);
+                                let mut lock = self_addr.lock().await;
+                                match (&*lock, device.local_addr) {
+                                    (None, Some(addr)) => {
+                                        println!("Own address is {}", addr);
+                                        display_manager.lock().await.set_own_ip(addr);
+                                        *lock = Some(addr)
+                                    }
+                                    (_, None) => {
+                                        println!("Unable to find own address towards {}", v)
+                                    }
+                                    _ => {}
+                                }
+                                drop(lock);
+                                devices.push(device);
                             }
                             (false, _) => {
                                 println!("Got a message from an IPv6 sender")
@@ -300,3 +346,22 @@ impl Communicate {
         });
     }
 }
+
+/// Asks the routing table which local address reaches `peer`. Connecting a udp socket sends nothing.
+fn route_local_addr(peer: SocketAddrV4, local_port: u16) -> Option<SocketAddrV4> {
+    let socket = match net::UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)) {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Unable to create routing socket: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = socket.connect(peer) {
+        println!("Unable to route to {}: {}", peer, e);
+        return None;
+    }
+    match socket.local_addr() {
+        Ok(SocketAddr::V4(v)) => Some(SocketAddrV4::new(*v.ip(), local_port)),
+        _ => None,
+    }
+}
@@ -0,0 +1,204 @@
+use {
+    crate::{display::Layout, hotkeys::Hotkey, remap::RemapRule, switch_guard::SwitchPolicies},
+    serde::{Deserialize, Serialize},
+    std::{env, error, fmt, fs, io, path::PathBuf},
+};
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+const CONFIG_ENV: &str = "SEAMLESS_CONFIG";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    IoError(io::Error),
+    SerdeError(serde_json::error::Error),
+    NotAnObject,
+}
+
+impl error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::IoError(e) => write!(f, "Config IO Error: {}", e),
+            ConfigError::SerdeError(e) => write!(f, "Config is not valid: {}", e),
+            ConfigError::NotAnObject => write!(f, "Config is not a json object"),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(value: io::Error) -> Self {
+        ConfigError::IoError(value)
+    }
+}
+
+impl From<serde_json::error::Error> for ConfigError {
+    fn from(value: serde_json::error::Error) -> Self {
+        ConfigError::SerdeError(value)
+    }
+}
+
+/// How keys are transmitted to other machines
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum KeyTransport {
+    /// The key name, pressed on the target through its own layout (`K_A` becomes `a`)
+    Layout,
+    /// The physical key position as a usb usage id, injected as a raw key on the target
+    Scancode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// The name other machines can use to refer to this one
+    pub name: String,
+    pub receive_directory: PathBuf,
+    /// Larger files are refused by the receiver
+    pub max_file_size: u64,
+    pub key_transport: KeyTransport,
+    pub key_remaps: Vec<RemapRule>,
+    pub hotkeys: Vec<Hotkey>,
+    pub switch_policies: SwitchPolicies,
+    pub layout: Layout,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            name: host_name(),
+            receive_directory: home_directory().join("Downloads").join("seamless"),
+            max_file_size: 4 * 1024 * 1024 * 1024,
+            key_transport: KeyTransport::Layout,
+            key_remaps: Vec::new(),
+            hotkeys: Vec::new(),
+            switch_policies: SwitchPolicies::default(),
+            layout: Layout::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        match env::var_os(CONFIG_ENV) {
+            Some(v) => PathBuf::from(v),
+            None => config_directory().join("seamless").join("config.json"),
+        }
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Config::path();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn load_or_default() -> Self {
+        match Config::load() {
+            Ok(v) => v,
+            Err(e) => {
+                println!("Unable to load config, using defaults: {}", e);
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Config::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Stores `layout` in the config file. Only the `layout` key of the file is replaced, so a
+    /// file that failed to load is never overwritten with the defaults.
+    pub fn save_layout(&mut self, layout: Layout) -> Result<()> {
+        self.layout = layout;
+        let path = Config::path();
+        if !path.exists() {
+            return self.save();
+        }
+        let mut file: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        match file.as_object_mut() {
+            Some(v) => {
+                v.insert(String::from("layout"), serde_json::to_value(&self.layout)?);
+            }
+            None => return Err(ConfigError::NotAnObject),
+        }
+        fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+fn host_name() -> String {
+    match env::var("HOSTNAME").or_else(|_| env::var("COMPUTERNAME")) {
+        Ok(v) => v,
+        Err(_) => match fs::read_to_string("/etc/hostname") {
+            Ok(v) => v.trim().to_string(),
+            Err(_) => String::from("seamless"),
+        },
+    }
+}
+
+fn home_directory() -> PathBuf {
+    match env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+        Some(v) => PathBuf::from(v),
+        None => PathBuf::from("."),
+    }
+}
+
+fn config_directory() -> PathBuf {
+    if let Some(v) = env::var_os("XDG_CONFIG_HOME") {
+        return PathBuf::from(v);
+    }
+    if let Some(v) = env::var_os("APPDATA") {
+        return PathBuf::from(v);
+    }
+    if cfg!(target_os = "macos") {
+        return home_directory().join("Library").join("Application Support");
+    }
+    home_directory().join(".config")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_the_layout_keeps_the_rest_of_the_file() {
+        let directory = env::temp_dir().join(format!("seamless-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.json");
+        env::set_var(CONFIG_ENV, &path);
+        let layout = Layout {
+            version: 3,
+            ..Layout::default()
+        };
+
+        //a config that does not load falls back to the defaults, which must not end up in the file
+        fs::write(&path, r#"{"name": "desk", "key_transport": "Unknown"}"#).unwrap();
+        assert!(Config::load().is_err());
+        let mut config = Config::load_or_default();
+        config.save_layout(layout.clone()).unwrap();
+        let file: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(file["name"], "desk");
+        assert_eq!(file["key_transport"], "Unknown");
+        assert_eq!(file["layout"]["version"], 3);
+
+        //a file that is no json at all is left alone
+        fs::write(&path, "{ broken").unwrap();
+        assert!(config.save_layout(layout.clone()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ broken");
+
+        //without a file the whole config is written
+        fs::remove_file(&path).unwrap();
+        config.save_layout(layout).unwrap();
+        assert_eq!(Config::load().unwrap().layout.version, 3);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::display::{Alignment, Edge},
+    async_trait::async_trait,
+    serde::{Deserialize, Serialize},
+    std::{
+        error, fmt,
+        io::{Read, Write},
+        net::{self, Ipv4Addr, SocketAddrV4},
+        path::PathBuf,
+        sync::Arc,
+    },
+    tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    },
+};
+
+type Result<T> = std::result::Result<T, ControlError>;
+
+#[derive(Debug)]
+pub enum ControlError {
+    IoError(std::io::Error),
+    SerdeError(serde_json::error::Error),
+    NotRunning,
+}
+
+impl error::Error for ControlError {}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlError::IoError(e) => write!(f, "Control IO Error: {}", e),
+            ControlError::SerdeError(e) => write!(f, "Control command is invalid: {}", e),
+            ControlError::NotRunning => write!(f, "No running seamless instance was found"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ControlError {
+    fn from(value: std::io::Error) -> Self {
+        ControlError::IoError(value)
+    }
+}
+
+impl From<serde_json::error::Error> for ControlError {
+    fn from(value: serde_json::error::Error) -> Self {
+        ControlError::SerdeError(value)
+    }
+}
+
+/// Commands the cli sends to the running instance
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ControlCommand {
+    SendFile(PathBuf),
+    SendText(String),
+    ToggleLock,
+    Status,
+    /// Places the first client at the given side of the second one
+    Place(String, Edge, String),
+    StartCalibration,
+    /// Records that the numbered machine sits at the edge the cursor is pushed against
+    CalibrateMachine(usize),
+    FinishCalibration,
+    /// The layout as json, see `display::Snapshot`
+    Snapshot,
+    SetAlignment(String, String, Alignment),
+    /// Files dropped into the drop window, they go to the peer beyond the edge the drag was held at
+    DropFiles(Vec<PathBuf>),
+}
+
+#[async_trait]
+pub trait ControlHandler
+where
+    Self: Sync + Send,
+{
+    async fn handle(&self, command: ControlCommand) -> String;
+}
+
+pub struct ControlServer {
+    listener: TcpListener,
+}
+
+impl ControlServer {
+    pub async fn new(port: u16) -> Result<Self> {
+        Ok(ControlServer {
+            listener: TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)).await?,
+        })
+    }
+
+    pub async fn listen(&self, handler: Arc<dyn ControlHandler>) {
+        loop {
+            let (stream, _) = match self.listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Error accepting control connection: {}", e);
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                if let Err(e) = ControlServer::handle_connection(stream, handler).await {
+                    println!("Error handling control connection: {}", e)
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(stream: TcpStream, handler: Arc<dyn ControlHandler>) -> Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        let command = serde_json::from_str::<ControlCommand>(line.trim())?;
+        let response = handler.handle(command).await;
+        reader.get_mut().write_all(response.as_bytes()).await?;
+        reader.get_mut().shutdown().await?;
+        Ok(())
+    }
+}
+
+pub async fn send_command(port: u16, command: &ControlCommand) -> Result<String> {
+    let mut stream = match TcpStream::connect(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)).await {
+        Ok(v) => v,
+        Err(_) => return Err(ControlError::NotRunning),
+    };
+    stream
+        .write_all((serde_json::to_string(command)? + "\n").as_bytes())
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response)
+}
+
+/// Like `send_command`, for callers outside of the runtime
+pub fn send_command_blocking(port: u16, command: &ControlCommand) -> Result<String> {
+    let mut stream = match net::TcpStream::connect(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port)) {
+        Ok(v) => v,
+        Err(_) => return Err(ControlError::NotRunning),
+    };
+    stream.write_all((serde_json::to_string(command)? + "\n").as_bytes())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
@@ -1,17 +1,28 @@
 use {
-    crate::{communicate::ReceiverDevice, input::MousePosition},
+    crate::{
+        communicate::ReceiverDevice,
+        input::{MouseMovement, MousePosition},
+    },
     display_info::DisplayInfo,
-    std::{error, fmt, net::SocketAddrV4, ptr},
+    std::{
+        error, fmt,
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    },
 };
 
 type Result<T> = std::result::Result<T, DisplayError>;
 
+/// How long displays of a client that is not connected (yet) are kept
+const PENDING_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Debug)]
 pub enum DisplayError {
     DisplayFetchError,
     ClientAddError,
-    OwnIpUnknown,
     InvalidMousePosition,
+    UnknownClient,
+    InvalidPlacement,
 }
 
 impl error::Error for DisplayError {}
@@ -23,30 +34,93 @@ impl fmt::Display for DisplayError {
             DisplayError::ClientAddError => {
                 write!(f, "Was unable to add client to connected displays list")
             }
-            DisplayError::OwnIpUnknown => write!(f, "Own Ip is unknown"),
             DisplayError::InvalidMousePosition => write!(f, "Invalid Mouse position"),
+            DisplayError::UnknownClient => write!(f, "There is no client with that name or ip"),
+            DisplayError::InvalidPlacement => {
+                write!(f, "A client can not be placed next to itself")
+            }
         }
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Edge {
     Left,
     Right,
+    Top,
+    Bottom,
+}
+
+/// How far the cursor is pushed past an edge when it crosses to another client
+pub const EDGE_STEP: i32 = 5;
+
+impl Edge {
+    pub const ALL: [Edge; 4] = [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom];
+
+    pub fn step(&self) -> MouseMovement {
+        match self {
+            Edge::Left => MouseMovement {
+                x: -EDGE_STEP,
+                y: 0,
+            },
+            Edge::Right => MouseMovement { x: EDGE_STEP, y: 0 },
+            Edge::Top => MouseMovement {
+                x: 0,
+                y: -EDGE_STEP,
+            },
+            Edge::Bottom => MouseMovement { x: 0, y: EDGE_STEP },
+        }
+    }
+
+    /// The edge a movement from `from` to `to` leaves through, decided by its dominant axis
+    pub fn between(from: &MousePosition, to: &MousePosition) -> Edge {
+        let x = to.x - from.x;
+        let y = to.y - from.y;
+        match (x.abs() >= y.abs(), x < 0, y < 0) {
+            (true, true, _) => Edge::Left,
+            (true, false, _) => Edge::Right,
+            (false, _, true) => Edge::Top,
+            (false, _, false) => Edge::Bottom,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum OsFamily {
+    Linux,
+    Windows,
+    MacOs,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+impl OsFamily {
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            OsFamily::Windows
+        } else if cfg!(target_os = "macos") {
+            OsFamily::MacOs
+        } else {
+            OsFamily::Linux
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub struct ClientDisplays {
     pub client: Client,
-    displays: Vec<Display>, //ordered
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub os: Option<OsFamily>,
+    displays: Vec<Display>, //primary first, positioned by client_x and client_y
 }
 
 impl ClientDisplays {
-    pub fn new_local() -> Result<Self> {
+    pub fn new_local(name: &str) -> Result<Self> {
         let mut info = match DisplayInfo::all() {
             Ok(v) => v,
             Err(_e) => return Err(DisplayError::DisplayFetchError),
         };
-        info.sort_by(|a, b| a.id.cmp(&b.id));
+        info.sort_by(|a, b| b.is_primary.cmp(&a.is_primary).then(a.id.cmp(&b.id)));
         let mut client_displays: Vec<Display> = Vec::new();
         for display in info.into_iter() {
             client_displays.push(display.into());
@@ -54,48 +128,245 @@ impl ClientDisplays {
 
         Ok(Self {
             client: Client::IsSelf,
+            name: Some(name.to_string()),
+            os: Some(OsFamily::current()),
             displays: client_displays,
         })
     }
 
-    pub fn on_horizontal_edge(&self, mouse_position: &MousePosition) -> Option<Edge> {
-        if self.displays.len() == 0 {
-            return None;
+    /// Whether `identifier` is the name or the ip of this client. This machine matches every
+    /// address in `own_addrs`, peers on different networks know it by different ones.
+    fn matches(&self, identifier: &str, own_addrs: &[SocketAddrV4]) -> bool {
+        if self.name.as_deref() == Some(identifier) {
+            return true;
         }
-        let display_left = self.displays.first().unwrap(); //this is ok because we checked the length of client_displays first
-        if mouse_position.x <= display_left.client_x {
-            return Some(Edge::Left);
+        match &self.client {
+            Client::IsNetworked(v) => v.ip().to_string() == identifier,
+            Client::IsSelf => own_addrs.iter().any(|v| v.ip().to_string() == identifier),
         }
+    }
 
-        let display_right = self.displays.last().unwrap(); //this is ok because we checked the length of client_displays first
-        if mouse_position.x >= display_right.client_x + display_left.width as i32 - 1 {
-            return Some(Edge::Right);
+    /// The name of this client, or its ip for clients that don't announce one
+    fn identifier(&self, own_ip: &Option<SocketAddrV4>) -> Option<String> {
+        if let Some(v) = &self.name {
+            return Some(v.clone());
         }
+        match (&self.client, own_ip) {
+            (Client::IsNetworked(v), _) => Some(v.ip().to_string()),
+            (Client::IsSelf, Some(v)) => Some(v.ip().to_string()),
+            (Client::IsSelf, None) => None,
+        }
+    }
 
-        None
+    /// The logical origin of every display. Starting at the first (primary) display, every display
+    /// is placed against a neighbour it touches in local coordinates, so displays with different
+    /// scale factors neither overlap nor leave gaps. Displays that touch no other one keep their
+    /// local origin divided by their own scale.
+    fn logical_origins(&self) -> Vec<MousePosition> {
+        let mut origins: Vec<Option<MousePosition>> = vec![None; self.displays.len()];
+        if let Some(first) = self.displays.first() {
+            origins[0] = Some(first.logical().0);
+        }
+        loop {
+            let mut placed = false;
+            for index in 0..self.displays.len() {
+                if origins[index].is_some() {
+                    continue;
+                }
+                for neighbour in 0..self.displays.len() {
+                    let origin = match &origins[neighbour] {
+                        Some(v) => v.clone(),
+                        None => continue,
+                    };
+                    if let Some(v) =
+                        self.displays[index].logical_next_to(&self.displays[neighbour], &origin)
+                    {
+                        origins[index] = Some(v);
+                        placed = true;
+                        break;
+                    }
+                }
+            }
+            if !placed {
+                break;
+            }
+        }
+        origins
+            .into_iter()
+            .zip(self.displays.iter())
+            .map(|(origin, display)| match origin {
+                Some(v) => v,
+                None => display.logical().0,
+            })
+            .collect()
+    }
+
+    /// The top left and bottom right corner of the area all displays of this client span in
+    /// logical pixels. Gaps between the displays stay part of the area.
+    fn bounds(&self) -> (MousePosition, MousePosition) {
+        let mut top_left = MousePosition { x: 0, y: 0 };
+        let mut bottom_right = MousePosition { x: 0, y: 0 };
+        let origins = self.logical_origins();
+        for (index, display) in self.displays.iter().enumerate() {
+            let origin = origins[index].clone();
+            let (_, width, height) = display.logical();
+            let right = origin.x + width as i32;
+            let bottom = origin.y + height as i32;
+            if index == 0 {
+                top_left = origin;
+                bottom_right = MousePosition {
+                    x: right,
+                    y: bottom,
+                };
+                continue;
+            }
+            top_left.x = top_left.x.min(origin.x);
+            top_left.y = top_left.y.min(origin.y);
+            bottom_right.x = bottom_right.x.max(right);
+            bottom_right.y = bottom_right.y.max(bottom);
+        }
+        (top_left, bottom_right)
+    }
+
+    /// The size of the block this client takes up in the layout, in logical pixels
+    fn size(&self) -> (u32, u32) {
+        let (top_left, bottom_right) = self.bounds();
+        (
+            (bottom_right.x - top_left.x) as u32,
+            (bottom_right.y - top_left.y) as u32,
+        )
+    }
+
+    /// The position of every display relative to the top left of this client's block
+    fn display_offsets(&self) -> Vec<MousePosition> {
+        let (top_left, _) = self.bounds();
+        self.logical_origins()
+            .into_iter()
+            .map(|origin| MousePosition {
+                x: origin.x - top_left.x,
+                y: origin.y - top_left.y,
+            })
+            .collect()
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub enum Client {
     IsSelf,
     IsNetworked(SocketAddrV4),
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 struct Display {
     pub id: u32,
     pub client_x: i32,
     pub client_y: i32,
     pub width: u32,
     pub height: u32,
+    /// Local coordinate units per logical pixel
+    #[serde(default = "default_scale_factor")]
+    pub scale_factor: f32,
+    /// Clockwise rotation in degrees
+    #[serde(default)]
+    pub rotation: f32,
+    /// The physical size, 0 if the display does not report it
+    #[serde(default)]
+    pub width_mm: i32,
+    #[serde(default)]
+    pub height_mm: i32,
+}
+
+fn default_scale_factor() -> f32 {
+    1.0
 }
 
 impl Display {
+    pub fn scale(&self) -> f32 {
+        if self.scale_factor > 0.0 {
+            self.scale_factor
+        } else {
+            1.0
+        }
+    }
+
+    /// The size of this display as it is oriented on the desk. Some platforms report the size of
+    /// the unrotated mode for portrait displays, so it is swapped if it doesn't match the rotation.
+    fn oriented_size(&self) -> (u32, u32) {
+        let portrait = (self.rotation.rem_euclid(180.0) - 90.0).abs() < 1.0;
+        if portrait && self.width > self.height {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    /// The origin of this display in logical pixels when it touches `other`, whose logical origin
+    /// is `other_origin`. The offset along the shared edge is measured in units of `other`.
+    fn logical_next_to(
+        &self,
+        other: &Display,
+        other_origin: &MousePosition,
+    ) -> Option<MousePosition> {
+        let (width, height) = self.oriented_size();
+        let (other_width, other_height) = other.oriented_size();
+        let (_, logical_width, logical_height) = self.logical();
+        let (_, other_logical_width, other_logical_height) = other.logical();
+        let along_x =
+            other_origin.x + ((self.client_x - other.client_x) as f32 / other.scale()) as i32;
+        let along_y =
+            other_origin.y + ((self.client_y - other.client_y) as f32 / other.scale()) as i32;
+        let overlaps_x = self.client_x < other.client_x + other_width as i32
+            && self.client_x + width as i32 > other.client_x;
+        let overlaps_y = self.client_y < other.client_y + other_height as i32
+            && self.client_y + height as i32 > other.client_y;
+
+        if overlaps_y && self.client_x == other.client_x + other_width as i32 {
+            return Some(MousePosition {
+                x: other_origin.x + other_logical_width as i32,
+                y: along_y,
+            });
+        }
+        if overlaps_y && self.client_x + width as i32 == other.client_x {
+            return Some(MousePosition {
+                x: other_origin.x - logical_width as i32,
+                y: along_y,
+            });
+        }
+        if overlaps_x && self.client_y == other.client_y + other_height as i32 {
+            return Some(MousePosition {
+                x: along_x,
+                y: other_origin.y + other_logical_height as i32,
+            });
+        }
+        if overlaps_x && self.client_y + height as i32 == other.client_y {
+            return Some(MousePosition {
+                x: along_x,
+                y: other_origin.y - logical_height as i32,
+            });
+        }
+        None
+    }
+
+    /// The position and size of this display in logical pixels. The position only holds for
+    /// displays without neighbours, see `ClientDisplays::logical_origins`.
+    fn logical(&self) -> (MousePosition, u32, u32) {
+        let scale = self.scale();
+        let (width, height) = self.oriented_size();
+        (
+            MousePosition {
+                x: (self.client_x as f32 / scale) as i32,
+                y: (self.client_y as f32 / scale) as i32,
+            },
+            (width as f32 / scale) as u32,
+            (height as f32 / scale) as u32,
+        )
+    }
+
     pub fn contains(&self, mouse_position: &MousePosition) -> bool {
-        (mouse_position.x >= self.client_x && mouse_position.x <= self.client_x + self.width as i32)
+        let (width, height) = self.oriented_size();
+        (mouse_position.x >= self.client_x && mouse_position.x < self.client_x + width as i32)
             && (mouse_position.y >= self.client_y
-                && mouse_position.y <= self.client_y + self.height as i32)
+                && mouse_position.y < self.client_y + height as i32)
     }
 }
 
@@ -107,22 +378,200 @@ impl From<DisplayInfo> for Display {
             client_y: value.y,
             width: value.width,
             height: value.height,
+            scale_factor: if cfg!(target_os = "macos") {
+                1.0 //macos already reports displays in logical points
+            } else {
+                value.scale_factor
+            },
+            rotation: value.rotation,
+            width_mm: value.width_mm,
+            height_mm: value.height_mm,
+        }
+    }
+}
+
+/// Where a client is placed in the layout grid. `client` is the name or the ip of the client.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct GridPlacement {
+    pub client: String,
+    pub column: i32,
+    pub row: i32,
+}
+
+/// How positions along the edge between two clients of different sizes are mapped onto each other
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Alignment {
+    /// The edges are stretched onto each other
+    #[default]
+    Proportional,
+    /// The top edges line up (the left edges for clients above each other)
+    Start,
+    /// The centers line up
+    Center,
+    /// A position on `to` is the position on `from` moved by this many pixels
+    Offset(i32),
+}
+
+impl Alignment {
+    /// Maps `position` on an edge of `from_length` pixels to an edge of `to_length` pixels
+    fn map(&self, position: i32, from_length: u32, to_length: u32) -> i32 {
+        let from_length = from_length.max(1) as i32;
+        let to_length = to_length.max(1) as i32;
+        let position = position.max(0).min(from_length - 1);
+        let mapped = match self {
+            Alignment::Proportional => {
+                (position as i64 * to_length as i64 / from_length as i64) as i32
+            }
+            Alignment::Start => position,
+            Alignment::Center => position + (to_length - from_length) / 2,
+            Alignment::Offset(v) => position + v,
+        };
+        mapped.max(0).min(to_length - 1)
+    }
+
+    /// The alignment for crossing the link in the other direction
+    fn inverse(&self) -> Alignment {
+        match self {
+            Alignment::Offset(v) => Alignment::Offset(-v),
+            v => *v,
         }
     }
 }
 
+/// The alignment between two neighbouring clients, given by name or ip
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Link {
+    pub from: String,
+    pub to: String,
+    pub alignment: Alignment,
+}
+
+/// A stretch of one edge of a display. `start` and `end` are logical pixels from the top (or the
+/// left) of the edge and default to the whole edge.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct EdgeSegment {
+    pub client: String,
+    /// The id of the display
+    pub display: u32,
+    pub edge: Edge,
+    #[serde(default)]
+    pub start: Option<u32>,
+    #[serde(default)]
+    pub end: Option<u32>,
+}
+
+/// Connects two edge segments. The cursor crosses it in both directions.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct EdgeLink {
+    pub from: EdgeSegment,
+    pub to: EdgeSegment,
+}
+
+/// An area of a display in which the cursor never switches clients. Logical pixels relative to
+/// the top left of the display.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct DeadZone {
+    pub client: String,
+    /// The id of the display
+    pub display: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DeadZones {
+    /// Pixels at both ends of every edge that never switch clients
+    pub corners: u32,
+    pub zones: Vec<DeadZone>,
+}
+
+/// The layout shared by all peers. Every change increases the version, peers adopt the layout
+/// with the highest version.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Layout {
+    pub version: u64,
+    pub grid: Vec<GridPlacement>,
+    pub links: Vec<Link>,
+    /// Once set, the cursor only crosses between clients through these and every other edge is
+    /// a wall
+    pub edge_links: Vec<EdgeLink>,
+    /// Leaving the outermost client of a row or column enters the one at the other end
+    pub wrap_around: bool,
+    pub dead_zones: DeadZones,
+}
+
+impl Layout {
+    /// Whether this layout replaces `other`. Layouts of the same version are ordered by their
+    /// content, so peers that changed the layout at the same time still agree on one.
+    pub fn supersedes(&self, other: &Layout) -> bool {
+        if self.version != other.version {
+            return self.version > other.version;
+        }
+        let own = serde_json::to_string(self).unwrap_or_default();
+        let other = serde_json::to_string(other).unwrap_or_default();
+        own > other
+    }
+}
+
+/// A display at its position in the global coordinate space
+#[derive(Debug, Clone)]
+struct PlacedDisplay {
+    client_index: usize,
+    display_index: usize,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    /// Local coordinate units per logical pixel of the display
+    scale: f32,
+}
+
+impl PlacedDisplay {
+    fn contains(&self, mouse_position: &MousePosition) -> bool {
+        mouse_position.x >= self.x
+            && mouse_position.x < self.x + self.width as i32
+            && mouse_position.y >= self.y
+            && mouse_position.y < self.y + self.height as i32
+    }
+}
+
 #[derive(Debug)]
 pub struct DisplayManager {
     clients: Vec<ClientDisplays>,
     own_ip: Option<SocketAddrV4>,
+    /// The address of this machine as every connected peer sees it, by the address of the peer
+    local_addrs: Vec<(SocketAddrV4, SocketAddrV4)>,
+    /// `own_ip` and every address in `local_addrs`
+    own_addrs: Vec<SocketAddrV4>,
+    layout: Layout,
+    /// The grid cell of every client, in the order of `clients`
+    cells: Vec<(i32, i32)>,
+    /// The top left corner of every client's block, in the order of `clients`
+    origins: Vec<MousePosition>,
+    placed_displays: Vec<PlacedDisplay>,
+    /// Displays of clients that are not connected (yet), with the time they were put aside
+    pending: Vec<(Instant, ClientDisplays)>,
 }
 
 impl DisplayManager {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            clients: vec![ClientDisplays::new_local()?],
+    pub fn new(name: &str, layout: Layout) -> Result<Self> {
+        let mut display_manager = Self {
+            clients: vec![ClientDisplays::new_local(name)?],
             own_ip: None,
-        })
+            local_addrs: Vec::new(),
+            own_addrs: Vec::new(),
+            layout,
+            cells: Vec::new(),
+            origins: Vec::new(),
+            placed_displays: Vec::new(),
+            pending: Vec::new(),
+        };
+        display_manager.update_layout();
+        Ok(display_manager)
     }
 
     pub fn received_displays(&mut self, client_displays: ClientDisplays) -> Result<()> {
@@ -150,29 +599,334 @@ impl DisplayManager {
             None => {}
         };
 
+        self.pending
+            .retain(|v| v.1.client != Client::IsNetworked(client_ipv4));
         self.clients.push(client_displays);
 
-        self.sort_client_displays()?;
-
+        self.sort_client_displays();
+        self.update_layout();
         Ok(())
     }
 
+    /// Replaces the displays of this machine. Returns whether they changed.
+    pub fn update_own_displays(&mut self, client_displays: ClientDisplays) -> bool {
+        let own_index = match self.get_own_client_displays_index() {
+            Some(v) => v,
+            None => return false,
+        };
+        if self.clients[own_index] == client_displays {
+            return false;
+        }
+        self.clients[own_index] = client_displays;
+        self.update_layout();
+        true
+    }
+
+    pub fn own_displays_changed(&self, client_displays: &ClientDisplays) -> bool {
+        match self.get_own_client_displays_index() {
+            Some(v) => &self.clients[v] != client_displays,
+            None => false,
+        }
+    }
+
+    /// Keeps the clients in `connected_clients`. Displays of other clients are kept aside for
+    /// `PENDING_TIMEOUT`, as their announcement can arrive before the client is known.
     pub fn filter_clients(&mut self, connected_clients: &Vec<ReceiverDevice>) {
-        self.clients = self
-            .clients
-            .drain(..)
-            .filter(|v| match v.client {
-                Client::IsSelf => return true,
-                Client::IsNetworked(addr) => {
-                    for client in connected_clients {
-                        if client.socket_addr == addr {
-                            return true;
+        let is_connected = |v: &ClientDisplays| match v.client {
+            Client::IsSelf => true,
+            Client::IsNetworked(addr) => connected_clients.iter().any(|v| v.socket_addr == addr),
+        };
+
+        let mut clients = Vec::new();
+        for client in self.clients.drain(..) {
+            if is_connected(&client) {
+                clients.push(client);
+            } else {
+                self.pending.push((Instant::now(), client));
+            }
+        }
+        let mut pending = Vec::new();
+        for (since, client) in self.pending.drain(..) {
+            if is_connected(&client) {
+                println!("Adding pending displays of {:?}", client.client);
+                clients.push(client);
+            } else if since.elapsed() < PENDING_TIMEOUT {
+                pending.push((since, client));
+            }
+        }
+        self.clients = clients;
+        self.pending = pending;
+        self.local_addrs = connected_clients
+            .iter()
+            .filter_map(|v| Some((v.socket_addr, v.local_addr?)))
+            .collect();
+        self.update_own_addrs();
+
+        self.sort_client_displays();
+        self.update_layout();
+    }
+
+    pub fn get_client_os(&self, addr: &SocketAddrV4) -> Option<OsFamily> {
+        for client in self.clients.iter() {
+            match client.client {
+                Client::IsNetworked(v) if &v == addr => return client.os,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Finds a client by its announced name or its ip
+    pub fn find_client(&self, name: &str) -> Option<Client> {
+        let index = self.find_client_index(name)?;
+        Some(self.clients[index].client.clone())
+    }
+
+    fn find_client_index(&self, name: &str) -> Option<usize> {
+        self.clients
+            .iter()
+            .position(|v| v.matches(name, &self.own_addrs))
+    }
+
+    /// Places `client` at the `edge` side of `neighbour`. A client already in that cell swaps
+    /// places with `client`. Every connected client is pinned to its cell, so machines that
+    /// join later don't move them. Returns the new layout.
+    pub fn place(&mut self, client: &str, edge: Edge, neighbour: &str) -> Result<Layout> {
+        let client_index = match self.find_client_index(client) {
+            Some(v) => v,
+            None => return Err(DisplayError::UnknownClient),
+        };
+        let neighbour_index = match self.find_client_index(neighbour) {
+            Some(v) => v,
+            None => return Err(DisplayError::UnknownClient),
+        };
+        if client_index == neighbour_index {
+            return Err(DisplayError::InvalidPlacement);
+        }
+
+        let (column, row) = self.cells[neighbour_index];
+        let cell = match edge {
+            Edge::Left => (column - 1, row),
+            Edge::Right => (column + 1, row),
+            Edge::Top => (column, row - 1),
+            Edge::Bottom => (column, row + 1),
+        };
+        let mut cells = self.cells.clone();
+        if let Some(occupant) = cells.iter().position(|v| v == &cell) {
+            cells[occupant] = cells[client_index];
+        }
+        cells[client_index] = cell;
+
+        let mut grid: Vec<GridPlacement> = Vec::new();
+        for (client, (column, row)) in self.clients.iter().zip(cells.iter()) {
+            if let Some(identifier) = client.identifier(&self.own_ip) {
+                grid.push(GridPlacement {
+                    client: identifier,
+                    column: *column,
+                    row: *row,
+                });
+            }
+        }
+        for placement in self.layout.grid.drain(..) {
+            let connected = self
+                .clients
+                .iter()
+                .any(|v| v.matches(&placement.client, &self.own_addrs));
+            let taken = grid
+                .iter()
+                .any(|v| v.column == placement.column && v.row == placement.row);
+            if !connected && !taken {
+                grid.push(placement);
+            }
+        }
+
+        self.layout = Layout {
+            version: self.layout.version + 1,
+            grid,
+            links: self.layout.links.clone(),
+            edge_links: self.layout.edge_links.clone(),
+            wrap_around: self.layout.wrap_around,
+            dead_zones: self.layout.dead_zones.clone(),
+        };
+        self.update_layout();
+        Ok(self.layout.clone())
+    }
+
+    /// The name (or ip) of every connected peer in layout order
+    pub fn get_peer_identifiers(&self) -> Vec<(Client, String)> {
+        self.clients
+            .iter()
+            .filter(|v| v.client != Client::IsSelf)
+            .filter_map(|v| Some((v.client.clone(), v.identifier(&self.own_ip)?)))
+            .collect()
+    }
+
+    pub fn get_own_identifier(&self) -> Option<String> {
+        self.clients[self.get_own_client_displays_index()?].identifier(&self.own_ip)
+    }
+
+    /// The outer edge of this machine's displays that `position` is closest to
+    pub fn get_pushed_edge(&self, position: &MousePosition) -> Option<Edge> {
+        let own_index = self.get_own_client_displays_index()?;
+        let origin = self.origins.get(own_index)?;
+        let size = self.clients[own_index].size();
+        let distances = [
+            (position.x - origin.x, Edge::Left),
+            (origin.x + size.0 as i32 - 1 - position.x, Edge::Right),
+            (position.y - origin.y, Edge::Top),
+            (origin.y + size.1 as i32 - 1 - position.y, Edge::Bottom),
+        ];
+        distances.iter().min_by_key(|v| v.0.abs()).map(|v| v.1)
+    }
+
+    /// Sets the alignment of the link between two clients, replacing the one set before
+    pub fn set_alignment(&mut self, from: &str, to: &str, alignment: Alignment) -> Result<Layout> {
+        if self.find_client_index(from).is_none() || self.find_client_index(to).is_none() {
+            return Err(DisplayError::UnknownClient);
+        }
+        self.layout
+            .links
+            .retain(|v| !((v.from == from && v.to == to) || (v.from == to && v.to == from)));
+        self.layout.links.push(Link {
+            from: from.to_string(),
+            to: to.to_string(),
+            alignment,
+        });
+        self.layout.version += 1;
+        self.update_layout();
+        Ok(self.layout.clone())
+    }
+
+    /// The state of the layout with `cursor` as the global cursor position
+    pub fn snapshot(&self, cursor: &MousePosition, locked: bool) -> Snapshot {
+        let mut clients = Vec::new();
+        for (index, client) in self.clients.iter().enumerate() {
+            let size = client.size();
+            clients.push(ClientSnapshot {
+                client: client.client.clone(),
+                name: client.identifier(&self.own_ip),
+                cell: self.cells[index],
+                x: self.origins[index].x,
+                y: self.origins[index].y,
+                width: size.0,
+                height: size.1,
+                displays: self
+                    .placed_displays
+                    .iter()
+                    .filter(|v| v.client_index == index)
+                    .map(|v| {
+                        let display = &client.displays[v.display_index];
+                        DisplaySnapshot {
+                            id: display.id,
+                            local_x: display.client_x,
+                            local_y: display.client_y,
+                            x: v.x,
+                            y: v.y,
+                            width: v.width,
+                            height: v.height,
+                            scale_factor: display.scale(),
+                            rotation: display.rotation,
+                            width_mm: display.width_mm,
+                            height_mm: display.height_mm,
                         }
-                    }
-                    return false;
-                }
-            })
+                    })
+                    .collect(),
+            });
+        }
+
+        Snapshot {
+            layout: self.layout.clone(),
+            clients,
+            own_index: self.get_own_client_displays_index(),
+            own_ip: self.own_ip,
+            cursor_position: (cursor.x, cursor.y),
+            cursor_owner: self.get_local_mouse_position(cursor).ok().map(|v| v.client),
+            locked,
+        }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// A hash of where every display sits in the global coordinate space. Peers with the same
+    /// layout version can still disagree on it while one of them misses a display update.
+    pub fn displays_hash(&self) -> u64 {
+        let mut displays: Vec<(i32, i32, u32, u32)> = self
+            .placed_displays
+            .iter()
+            .map(|v| (v.x, v.y, v.width, v.height))
             .collect();
+        displays.sort();
+        //fnv-1a, it has to give the same result on every build of every peer
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (x, y, width, height) in displays {
+            for value in [x as u32, y as u32, width, height] {
+                for byte in value.to_be_bytes() {
+                    hash ^= byte as u64;
+                    hash = hash.wrapping_mul(0x100000001b3);
+                }
+            }
+        }
+        hash
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.update_layout();
+    }
+
+    /// The client `offset` places away from `client` in the layout order, wrapping around at the ends
+    pub fn get_neighbour_client(&self, client: &Client, offset: i32) -> Option<Client> {
+        let index = self.clients.iter().position(|v| &v.client == client)? as i32;
+        let length = self.clients.len() as i32;
+        let neighbour = (index + offset).rem_euclid(length) as usize;
+        Some(self.clients[neighbour].client.clone())
+    }
+
+    pub fn get_client_center(&self, client: &Client) -> Result<MousePosition> {
+        let client_index = match self.clients.iter().position(|v| &v.client == client) {
+            Some(v) => v,
+            None => return Err(DisplayError::DisplayFetchError),
+        };
+        let display = match self
+            .placed_displays
+            .iter()
+            .find(|v| v.client_index == client_index && v.display_index == 0)
+        {
+            Some(v) => v,
+            None => return Err(DisplayError::DisplayFetchError),
+        };
+
+        Ok(MousePosition {
+            x: display.x + display.width as i32 / 2,
+            y: display.y + display.height as i32 / 2,
+        })
+    }
+
+    /// Where a cursor at `position` is after the layout changed. A cursor whose display is gone
+    /// moves to the center of the primary display of its client, or of this machine if the client
+    /// left as well.
+    pub fn reposition(&self, position: &ClientMousePosition) -> Result<MousePosition> {
+        match self.get_client_global_mouse_position(position) {
+            Ok(v) => Ok(v),
+            Err(_) => match self.get_client_center(&position.client) {
+                Ok(v) => Ok(v),
+                Err(_) => self.get_client_center(&Client::IsSelf),
+            },
+        }
+    }
+
+    /// Local coordinate units per logical pixel on the primary display of this machine
+    pub fn get_own_scale_factor(&self) -> f32 {
+        match self
+            .get_own_client_displays_index()
+            .and_then(|v| self.clients[v].displays.first())
+        {
+            Some(v) => v.scale(),
+            None => 1.0,
+        }
     }
 
     pub fn get_own_client_displays_index(&self) -> Option<usize> {
@@ -188,129 +942,432 @@ impl DisplayManager {
         None
     }
 
+    /// Returns the edge `mouse_position` rests against if it leaves this client and another client
+    /// lies in that direction
     pub fn is_on_edge(
         &self,
         mouse_position: &MousePosition,
         client_index: usize,
     ) -> Result<Option<Edge>> {
-        let client = self.clients.get(client_index);
-        match client {
-            Some(v) => {
-                let edge = v.on_horizontal_edge(mouse_position);
-                match edge {
-                    Some(v) => {
-                        match (client_index > 0, client_index < self.clients.len() - 1, &v) {
-                            (true, _, Edge::Left) => Ok(Some(v)),
-                            (_, true, Edge::Right) => Ok(Some(v)),
-                            _ => Ok(None),
-                        }
-                    }
-                    None => Ok(None),
-                }
+        let global_position = self.get_client_global_position(client_index, mouse_position)?;
+        let display = match self.get_placed_display(&global_position) {
+            Some(v) => v,
+            None => return Err(DisplayError::InvalidMousePosition),
+        };
+
+        for edge in Edge::ALL {
+            let on_edge = match edge {
+                Edge::Left => global_position.x <= display.x,
+                Edge::Right => global_position.x >= display.x + display.width as i32 - 1,
+                Edge::Top => global_position.y <= display.y,
+                Edge::Bottom => global_position.y >= display.y + display.height as i32 - 1,
+            };
+            if !on_edge {
+                continue;
+            }
+            match self.get_placed_display(&(global_position.clone() + edge.step())) {
+                Some(v) if v.client_index == client_index => continue,
+                _ => {}
+            }
+            if self.in_dead_zone(display, edge, &global_position) {
+                continue;
+            }
+            let linked = if self.layout.edge_links.is_empty() {
+                //the inner corner of an arrangement like an L is a wall, as it is for `cross`
+                !self.in_client_block(client_index, &(global_position.clone() + edge.step()))
+                    && self.get_neighbour_index(client_index, edge).is_some()
+            } else {
+                self.get_link_target(display, edge, &global_position)
+                    .is_some()
+            };
+            if linked {
+                return Ok(Some(edge));
             }
-            None => Err(DisplayError::DisplayFetchError),
         }
+
+        Ok(None)
     }
 
-    fn sort_client_displays(&mut self) -> Result<()> {
-        let own_ip = match self.own_ip {
-            Some(v) => v,
-            None => {
-                return Err(DisplayError::OwnIpUnknown);
+    /// The closest client beyond `edge` of the client at `client_index`, in the same row or column.
+    /// With wrap around the outermost client on the other side follows the last one.
+    fn get_neighbour_index(&self, client_index: usize, edge: Edge) -> Option<usize> {
+        let (column, row) = *self.cells.get(client_index)?;
+        let in_line: Vec<(i32, usize)> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (c, r))| {
+                let distance = match edge {
+                    Edge::Left if *r == row => column - c,
+                    Edge::Right if *r == row => c - column,
+                    Edge::Top if *c == column => row - r,
+                    Edge::Bottom if *c == column => r - row,
+                    _ => return None,
+                };
+                if distance != 0 {
+                    Some((distance, index))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let neighbour = in_line.iter().filter(|v| v.0 > 0).min();
+        match neighbour {
+            Some(v) => Some(v.1),
+            None if self.layout.wrap_around => in_line.iter().min().map(|v| v.1),
+            None => None,
+        }
+    }
+
+    fn get_alignment(&self, from: usize, to: usize) -> Alignment {
+        let from = &self.clients[from];
+        let to = &self.clients[to];
+        for link in self.layout.links.iter() {
+            if from.matches(&link.from, &self.own_addrs) && to.matches(&link.to, &self.own_addrs) {
+                return link.alignment;
             }
-        };
+            if to.matches(&link.from, &self.own_addrs) && from.matches(&link.to, &self.own_addrs) {
+                return link.alignment.inverse();
+            }
+        }
+        Alignment::default()
+    }
 
-        self.clients.sort_by(|a, b| {
-            let a_ipv4 = match a.client {
-                Client::IsNetworked(v) => v,
-                Client::IsSelf => own_ip,
+    /// Where the cursor lands when it moves from `from` to `to` and leaves the client under
+    /// `from`, with the edge it leaves through. None if `to` stays within that client or no client
+    /// lies in that direction. The cursor is kept on the client inside dead zones and, with edge
+    /// links, when no link is hit.
+    pub fn cross(&self, from: &MousePosition, to: &MousePosition) -> Option<(MousePosition, Edge)> {
+        let display = self.get_placed_display(from)?;
+        let client_index = display.client_index;
+        match self.get_placed_display(to) {
+            Some(v) if v.client_index == client_index => return None,
+            _ => {}
+        }
+        let edge = Edge::between(from, to);
+        let clamped = MousePosition {
+            x: to
+                .x
+                .max(display.x)
+                .min(display.x + display.width as i32 - 1),
+            y: to
+                .y
+                .max(display.y)
+                .min(display.y + display.height as i32 - 1),
+        };
+        if self.in_dead_zone(display, edge, &clamped) {
+            return Some((clamped, edge));
+        }
+        if !self.layout.edge_links.is_empty() {
+            return match self.get_link_target(display, edge, &clamped) {
+                Some(v) => Some((v, edge)),
+                None => Some((clamped, edge)),
             };
+        }
 
-            let b_ipv4 = match b.client {
-                Client::IsNetworked(v) => v,
-                Client::IsSelf => own_ip,
-            };
+        if self.in_client_block(client_index, to) {
+            return None;
+        }
+        let origin = &self.origins[client_index];
+        let size = self.clients[client_index].size();
 
-            a_ipv4.cmp(&b_ipv4)
-        });
+        let neighbour_index = self.get_neighbour_index(client_index, edge)?;
+        let alignment = self.get_alignment(client_index, neighbour_index);
+        let neighbour_origin = &self.origins[neighbour_index];
+        let neighbour_size = self.clients[neighbour_index].size();
+        let landing = match edge {
+            Edge::Left | Edge::Right => MousePosition {
+                x: match edge {
+                    Edge::Left => neighbour_origin.x + neighbour_size.0 as i32 - 1,
+                    _ => neighbour_origin.x,
+                },
+                y: neighbour_origin.y + alignment.map(to.y - origin.y, size.1, neighbour_size.1),
+            },
+            Edge::Top | Edge::Bottom => MousePosition {
+                x: neighbour_origin.x + alignment.map(to.x - origin.x, size.0, neighbour_size.0),
+                y: match edge {
+                    Edge::Top => neighbour_origin.y + neighbour_size.1 as i32 - 1,
+                    _ => neighbour_origin.y,
+                },
+            },
+        };
 
-        Ok(())
+        self.snap_to_client(neighbour_index, &landing)
+            .map(|v| (v, edge))
     }
 
-    pub fn set_own_ip(&mut self, own_ip: SocketAddrV4) {
-        self.own_ip = Some(own_ip);
-        self.sort_client_displays().unwrap(); //this is valid because sort_client_displays only returns one possible error, which is covered by setting own_ip
+    /// Whether `position` lies within the block of the client at `client_index`, gaps between its
+    /// displays included
+    fn in_client_block(&self, client_index: usize, position: &MousePosition) -> bool {
+        let origin = &self.origins[client_index];
+        let size = self.clients[client_index].size();
+        position.x >= origin.x
+            && position.x < origin.x + size.0 as i32
+            && position.y >= origin.y
+            && position.y < origin.y + size.1 as i32
     }
 
-    pub fn get_local_mouse_position(
+    /// Whether leaving `display` through `edge` at `position` is prevented by a dead zone
+    fn in_dead_zone(&self, display: &PlacedDisplay, edge: Edge, position: &MousePosition) -> bool {
+        let x = position.x - display.x;
+        let y = position.y - display.y;
+        let (along, length) = match edge {
+            Edge::Left | Edge::Right => (y, display.height as i32),
+            Edge::Top | Edge::Bottom => (x, display.width as i32),
+        };
+        let corners = self.layout.dead_zones.corners as i32;
+        if along < corners || along >= length - corners {
+            return true;
+        }
+
+        let client = &self.clients[display.client_index];
+        let id = client.displays[display.display_index].id;
+        self.layout.dead_zones.zones.iter().any(|v| {
+            v.display == id
+                && client.matches(&v.client, &self.own_addrs)
+                && x >= v.x as i32
+                && x < (v.x + v.width) as i32
+                && y >= v.y as i32
+                && y < (v.y + v.height) as i32
+        })
+    }
+
+    /// The range `segment` covers on the edge of `display`, if it belongs to that display
+    fn get_segment_range(
         &self,
-        mouse_position: &MousePosition,
-    ) -> Result<ClientMousePosition> {
-        struct FoundDisplay<'a> {
-            pub client_displays: &'a ClientDisplays,
-            pub display: &'a Display,
-            pub display_position: MousePosition,
-        }
-
-        impl<'a> FoundDisplay<'a> {
-            pub fn new(
-                client_displays: &ClientDisplays,
-                display_index: usize,
-                display_position: MousePosition,
-            ) -> Result<FoundDisplay> {
-                let display = client_displays.displays.get(display_index).unwrap(); //is ok because i just gave you my index
-                if display_position.x < 0
-                    || display_position.y < 0
-                    || display.width < display_position.x as u32
-                    || display.height < display_position.y as u32
-                {
-                    return Err(DisplayError::InvalidMousePosition);
+        segment: &EdgeSegment,
+        display: &PlacedDisplay,
+    ) -> Option<(i32, i32)> {
+        let client = &self.clients[display.client_index];
+        if client.displays[display.display_index].id != segment.display
+            || !client.matches(&segment.client, &self.own_addrs)
+        {
+            return None;
+        }
+        let length = match segment.edge {
+            Edge::Left | Edge::Right => display.height,
+            Edge::Top | Edge::Bottom => display.width,
+        } as i32;
+        let start = segment.start.map(|v| v as i32).unwrap_or(0).min(length);
+        let end = segment.end.map(|v| v as i32).unwrap_or(length).min(length);
+        Some((start, end))
+    }
+
+    /// Where the cursor lands when it leaves `display` through `edge` at `position`, if a linked
+    /// segment covers that point
+    fn get_link_target(
+        &self,
+        display: &PlacedDisplay,
+        edge: Edge,
+        position: &MousePosition,
+    ) -> Option<MousePosition> {
+        let position = match edge {
+            Edge::Left | Edge::Right => position.y - display.y,
+            Edge::Top | Edge::Bottom => position.x - display.x,
+        };
+        for link in self.layout.edge_links.iter() {
+            for (near, far) in [(&link.from, &link.to), (&link.to, &link.from)] {
+                if near.edge != edge {
+                    continue;
+                }
+                let (start, end) = match self.get_segment_range(near, display) {
+                    Some(v) => v,
+                    None => continue,
+                };
+                if position < start || position >= end {
+                    continue;
                 }
+                let (target, (target_start, target_end)) = match self
+                    .placed_displays
+                    .iter()
+                    .find_map(|v| self.get_segment_range(far, v).map(|range| (v, range)))
+                {
+                    Some(v) => v,
+                    None => continue,
+                };
+                let mapped = target_start
+                    + ((position - start) as i64 * (target_end - target_start) as i64
+                        / (end - start).max(1) as i64) as i32;
+                let mapped = mapped.min(target_end - 1).max(target_start);
+                return Some(match far.edge {
+                    Edge::Left => MousePosition {
+                        x: target.x,
+                        y: target.y + mapped,
+                    },
+                    Edge::Right => MousePosition {
+                        x: target.x + target.width as i32 - 1,
+                        y: target.y + mapped,
+                    },
+                    Edge::Top => MousePosition {
+                        x: target.x + mapped,
+                        y: target.y,
+                    },
+                    Edge::Bottom => MousePosition {
+                        x: target.x + mapped,
+                        y: target.y + target.height as i32 - 1,
+                    },
+                });
+            }
+        }
+        None
+    }
 
-                Ok(FoundDisplay {
-                    client_displays,
-                    display,
-                    display_position,
-                })
+    /// The point on a display of the client at `client_index` that is closest to `position`
+    fn snap_to_client(
+        &self,
+        client_index: usize,
+        position: &MousePosition,
+    ) -> Option<MousePosition> {
+        self.placed_displays
+            .iter()
+            .filter(|v| v.client_index == client_index)
+            .map(|v| MousePosition {
+                x: position.x.max(v.x).min(v.x + v.width as i32 - 1),
+                y: position.y.max(v.y).min(v.y + v.height as i32 - 1),
+            })
+            .min_by_key(|v| (v.x - position.x).abs() + (v.y - position.y).abs())
+    }
+
+    fn update_own_addrs(&mut self) {
+        let mut own_addrs: Vec<SocketAddrV4> = self.own_ip.into_iter().collect();
+        for (_, addr) in self.local_addrs.iter() {
+            if !own_addrs.contains(addr) {
+                own_addrs.push(*addr);
             }
+        }
+        self.own_addrs = own_addrs;
+    }
+
+    /// Sorts the clients by name, clients without a name follow by address. Unlike an address the
+    /// name of a machine is the same from every peer, so all peers agree on the order.
+    fn sort_client_displays(&mut self) {
+        let own_ip = self
+            .own_ip
+            .unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+        self.clients.sort_by_key(|v| {
+            let addr = match v.client {
+                Client::IsNetworked(v) => v,
+                Client::IsSelf => own_ip,
+            };
+            (v.name.is_none(), v.name.clone(), addr)
+        });
+    }
+
+    pub fn set_own_ip(&mut self, own_ip: SocketAddrV4) {
+        self.own_ip = Some(own_ip);
+        self.update_own_addrs();
+        self.sort_client_displays();
+        self.update_layout();
+    }
 
-            pub fn get_local_position(&self) -> MousePosition {
-                MousePosition {
-                    x: self.display.client_x + self.display_position.x,
-                    y: self.display.client_y + self.display_position.y,
+    /// Assigns every client a grid cell and places its displays in the global coordinate space.
+    /// Clients without a (free) cell in the grid are appended to the right of the first row.
+    fn update_layout(&mut self) {
+        let mut next_column = match self.layout.grid.iter().map(|v| v.column).max() {
+            Some(v) => v + 1,
+            None => 0,
+        };
+        let mut cells: Vec<(i32, i32)> = Vec::new();
+        for client in self.clients.iter() {
+            let placement = self
+                .layout
+                .grid
+                .iter()
+                .find(|v| client.matches(&v.client, &self.own_addrs))
+                .map(|v| (v.column, v.row))
+                .filter(|v| !cells.contains(v));
+            match placement {
+                Some(v) => cells.push(v),
+                None => {
+                    cells.push((next_column, 0));
+                    next_column += 1;
                 }
             }
         }
+        self.cells = cells.clone();
 
-        let mut current_x: u32 = 0;
-        let mut found_client: Option<FoundDisplay> = None;
-        'client_loop: for client in self.clients.iter() {
-            for (index, display) in client.displays.iter().enumerate() {
-                if current_x + display.width > mouse_position.x as u32 {
-                    found_client = Some(FoundDisplay::new(
-                        client,
-                        index,
-                        MousePosition {
-                            x: mouse_position.x - current_x as i32,
-                            y: mouse_position.y,
-                        },
-                    )?);
-                    break 'client_loop;
-                }
-                current_x += display.width;
+        let sizes: Vec<(u32, u32)> = self.clients.iter().map(|v| v.size()).collect();
+        let mut columns: Vec<i32> = cells.iter().map(|v| v.0).collect();
+        columns.sort();
+        columns.dedup();
+        let mut rows: Vec<i32> = cells.iter().map(|v| v.1).collect();
+        rows.sort();
+        rows.dedup();
+
+        let mut column_x = Vec::new();
+        let mut x = 0;
+        for column in columns.iter() {
+            column_x.push(x);
+            x += cells
+                .iter()
+                .zip(sizes.iter())
+                .filter(|(cell, _)| cell.0 == *column)
+                .map(|(_, size)| size.0)
+                .max()
+                .unwrap_or(0) as i32;
+        }
+        let mut row_y = Vec::new();
+        let mut y = 0;
+        for row in rows.iter() {
+            row_y.push(y);
+            y += cells
+                .iter()
+                .zip(sizes.iter())
+                .filter(|(cell, _)| cell.1 == *row)
+                .map(|(_, size)| size.1)
+                .max()
+                .unwrap_or(0) as i32;
+        }
+
+        self.placed_displays.clear();
+        self.origins.clear();
+        for (client_index, client) in self.clients.iter().enumerate() {
+            let (column, row) = cells[client_index];
+            let origin = MousePosition {
+                x: column_x[columns.binary_search(&column).unwrap()], //the column was collected from cells
+                y: row_y[rows.binary_search(&row).unwrap()],
+            };
+            self.origins.push(origin.clone());
+            for (display_index, offset) in client.display_offsets().into_iter().enumerate() {
+                let display = &client.displays[display_index];
+                let (_, width, height) = display.logical();
+                self.placed_displays.push(PlacedDisplay {
+                    client_index,
+                    display_index,
+                    x: origin.x + offset.x,
+                    y: origin.y + offset.y,
+                    width,
+                    height,
+                    scale: display.scale(),
+                });
             }
         }
+    }
 
-        let result = match found_client {
+    fn get_placed_display(&self, mouse_position: &MousePosition) -> Option<&PlacedDisplay> {
+        self.placed_displays
+            .iter()
+            .find(|v| v.contains(mouse_position))
+    }
+
+    pub fn get_local_mouse_position(
+        &self,
+        mouse_position: &MousePosition,
+    ) -> Result<ClientMousePosition> {
+        let placed = match self.get_placed_display(mouse_position) {
             Some(v) => v,
-            None => {
-                return Err(DisplayError::InvalidMousePosition);
-            }
+            None => return Err(DisplayError::InvalidMousePosition),
         };
+        let client = &self.clients[placed.client_index];
+        let display = &client.displays[placed.display_index];
 
         Ok(ClientMousePosition {
-            client: result.client_displays.client.clone(),
-            mouse_position: result.get_local_position(),
+            client: client.client.clone(),
+            mouse_position: MousePosition {
+                x: display.client_x + ((mouse_position.x - placed.x) as f32 * placed.scale) as i32,
+                y: display.client_y + ((mouse_position.y - placed.y) as f32 * placed.scale) as i32,
+            },
         })
     }
 
@@ -318,41 +1375,57 @@ impl DisplayManager {
         &self,
         local_position: MousePosition,
     ) -> Result<MousePosition> {
-        for client in self.clients.iter() {
-            match client.client {
-                Client::IsNetworked(_) => {}
-                Client::IsSelf => {
-                    for display in client.displays.iter() {
-                        if display.contains(&local_position) {
-                            return Ok(self.get_display_position(&display)?
-                                + MousePosition {
-                                    x: local_position.x - display.client_x,
-                                    y: local_position.y - display.client_y,
-                                });
-                        }
-                    }
-                }
-            }
+        match self.get_own_client_displays_index() {
+            Some(v) => self.get_client_global_position(v, &local_position),
+            None => Err(DisplayError::InvalidMousePosition),
         }
-
-        Err(DisplayError::InvalidMousePosition)
     }
 
-    fn get_display_position(&self, display: &Display) -> Result<MousePosition> {
-        let mut total_x: u32 = 0;
-        for client in self.clients.iter() {
-            for client_display in client.displays.iter() {
-                if ptr::eq(client_display, display) {
-                    return Ok(MousePosition {
-                        x: total_x as i32,
-                        y: 0,
-                    });
-                }
-                total_x += client_display.width;
-            }
+    /// The global position of a position on any client
+    pub fn get_client_global_mouse_position(
+        &self,
+        position: &ClientMousePosition,
+    ) -> Result<MousePosition> {
+        match self
+            .clients
+            .iter()
+            .position(|v| v.client == position.client)
+        {
+            Some(v) => self.get_client_global_position(v, &position.mouse_position),
+            None => Err(DisplayError::UnknownClient),
         }
+    }
 
-        Err(DisplayError::DisplayFetchError)
+    fn get_client_global_position(
+        &self,
+        client_index: usize,
+        local_position: &MousePosition,
+    ) -> Result<MousePosition> {
+        let client = match self.clients.get(client_index) {
+            Some(v) => v,
+            None => return Err(DisplayError::DisplayFetchError),
+        };
+        let display_index = match client
+            .displays
+            .iter()
+            .position(|v| v.contains(local_position))
+        {
+            Some(v) => v,
+            None => return Err(DisplayError::InvalidMousePosition),
+        };
+        let display = &client.displays[display_index];
+
+        match self
+            .placed_displays
+            .iter()
+            .find(|v| v.client_index == client_index && v.display_index == display_index)
+        {
+            Some(placed) => Ok(MousePosition {
+                x: placed.x + ((local_position.x - display.client_x) as f32 / placed.scale) as i32,
+                y: placed.y + ((local_position.y - display.client_y) as f32 / placed.scale) as i32,
+            }),
+            None => Err(DisplayError::DisplayFetchError),
+        }
     }
 
     pub fn get_missing_displays<'a>(
@@ -385,8 +1458,634 @@ impl DisplayManager {
     }
 }
 
+/// A serializable view of the layout. Positions are global logical pixels.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub layout: Layout,
+    pub clients: Vec<ClientSnapshot>,
+    /// The index of this machine in `clients`
+    pub own_index: Option<usize>,
+    pub own_ip: Option<SocketAddrV4>,
+    pub cursor_position: (i32, i32),
+    /// The client the cursor is on, None if the position is outside of every display
+    pub cursor_owner: Option<Client>,
+    /// Whether the cursor is locked to `cursor_owner`
+    pub locked: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ClientSnapshot {
+    pub client: Client,
+    /// The name, or the ip for clients that don't announce one
+    pub name: Option<String>,
+    pub cell: (i32, i32),
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub displays: Vec<DisplaySnapshot>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DisplaySnapshot {
+    pub id: u32,
+    /// The position the client itself reports for the display
+    pub local_x: i32,
+    pub local_y: i32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub rotation: f32,
+    #[serde(default)]
+    pub width_mm: i32,
+    #[serde(default)]
+    pub height_mm: i32,
+}
+
 #[derive(Debug)]
 pub struct ClientMousePosition {
     pub client: Client,
     pub mouse_position: MousePosition,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(x: i32, y: i32, width: u32, height: u32, scale_factor: f32) -> Display {
+        Display {
+            id: 0,
+            client_x: x,
+            client_y: y,
+            width,
+            height,
+            scale_factor,
+            rotation: 0.0,
+            width_mm: 0,
+            height_mm: 0,
+        }
+    }
+
+    /// The displays get the ids 1, 2, ... in the given order
+    fn client(client: Client, name: &str, mut displays: Vec<Display>) -> ClientDisplays {
+        for (index, display) in displays.iter_mut().enumerate() {
+            display.id = index as u32 + 1;
+        }
+        ClientDisplays {
+            client,
+            name: Some(name.to_string()),
+            os: None,
+            displays,
+        }
+    }
+
+    fn peer() -> Client {
+        Client::IsNetworked(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 31726))
+    }
+
+    /// This machine is `clients[0]`, the others follow it to the right in the given order
+    fn manager(clients: Vec<ClientDisplays>, layout: Layout) -> DisplayManager {
+        let mut manager = DisplayManager {
+            clients,
+            own_ip: None,
+            local_addrs: Vec::new(),
+            own_addrs: Vec::new(),
+            layout,
+            cells: Vec::new(),
+            origins: Vec::new(),
+            placed_displays: Vec::new(),
+            pending: Vec::new(),
+        };
+        manager.update_layout();
+        manager
+    }
+
+    fn cross(
+        manager: &DisplayManager,
+        from: (i32, i32),
+        to: (i32, i32),
+    ) -> Option<((i32, i32), Edge)> {
+        manager
+            .cross(
+                &MousePosition {
+                    x: from.0,
+                    y: from.1,
+                },
+                &MousePosition { x: to.0, y: to.1 },
+            )
+            .map(|(v, edge)| ((v.x, v.y), edge))
+    }
+
+    fn on_edge(manager: &DisplayManager, local: (i32, i32)) -> Option<Edge> {
+        manager
+            .is_on_edge(
+                &MousePosition {
+                    x: local.0,
+                    y: local.1,
+                },
+                0,
+            )
+            .unwrap()
+    }
+
+    /// Two displays on top of each other and one to the right of the lower one, next to a peer
+    /// with a single display
+    fn l_shape() -> DisplayManager {
+        manager(
+            vec![
+                client(
+                    Client::IsSelf,
+                    "a",
+                    vec![
+                        display(0, 0, 1920, 1080, 1.0),
+                        display(0, 1080, 1920, 1080, 1.0),
+                        display(1920, 1080, 1920, 1080, 1.0),
+                    ],
+                ),
+                client(peer(), "b", vec![display(0, 0, 1920, 1080, 1.0)]),
+            ],
+            Layout::default(),
+        )
+    }
+
+    /// A tall display next to a peer with a display half its height
+    fn tall_next_to_short(alignment: Alignment) -> DisplayManager {
+        manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 2160, 1.0)]),
+                client(peer(), "b", vec![display(0, 0, 1920, 1080, 1.0)]),
+            ],
+            Layout {
+                links: vec![Link {
+                    from: String::from("a"),
+                    to: String::from("b"),
+                    alignment,
+                }],
+                ..Layout::default()
+            },
+        )
+    }
+
+    /// This machine and two peers with a full hd display each, in a row or in a column
+    fn three_in_line(column: bool, wrap_around: bool) -> DisplayManager {
+        let other = Client::IsNetworked(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 3), 31726));
+        let grid = match column {
+            true => ["a", "b", "c"]
+                .iter()
+                .enumerate()
+                .map(|(row, name)| GridPlacement {
+                    client: name.to_string(),
+                    column: 0,
+                    row: row as i32,
+                })
+                .collect(),
+            false => Vec::new(),
+        };
+        manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 1080, 1.0)]),
+                client(peer(), "b", vec![display(0, 0, 1920, 1080, 1.0)]),
+                client(other, "c", vec![display(0, 0, 1920, 1080, 1.0)]),
+            ],
+            Layout {
+                grid,
+                wrap_around,
+                ..Layout::default()
+            },
+        )
+    }
+
+    fn origins(client: &ClientDisplays) -> Vec<(i32, i32)> {
+        client
+            .logical_origins()
+            .into_iter()
+            .map(|v| (v.x, v.y))
+            .collect()
+    }
+
+    #[test]
+    fn mixed_scale_displays_touch_in_logical_pixels() {
+        //a 4k display at 200% with a full hd display at 100% to its right and below it
+        let machine = client(
+            Client::IsSelf,
+            "a",
+            vec![
+                display(0, 0, 3840, 2160, 2.0),
+                display(3840, 1080, 1920, 1080, 1.0),
+                display(1920, 2160, 1920, 1080, 1.0),
+            ],
+        );
+        assert_eq!(origins(&machine), vec![(0, 0), (1920, 540), (960, 1080)]);
+        assert_eq!(machine.size(), (3840, 2160));
+    }
+
+    #[test]
+    fn displays_left_of_the_primary_are_placed_against_it() {
+        let machine = client(
+            Client::IsSelf,
+            "a",
+            vec![
+                display(0, 0, 2880, 1800, 2.0),
+                display(-2560, -200, 2560, 1440, 1.0),
+            ],
+        );
+        assert_eq!(origins(&machine), vec![(0, 0), (-2560, -100)]);
+    }
+
+    #[test]
+    fn displays_without_neighbours_keep_their_own_origin() {
+        let machine = client(
+            Client::IsSelf,
+            "a",
+            vec![
+                display(0, 0, 1920, 1080, 1.0),
+                display(4000, 0, 2000, 1000, 2.0),
+            ],
+        );
+        assert_eq!(origins(&machine), vec![(0, 0), (2000, 0)]);
+    }
+
+    #[test]
+    fn l_shape_crosses_from_the_outer_edge() {
+        let manager = l_shape();
+        assert_eq!(
+            cross(&manager, (3839, 1500), (3840, 1500)),
+            Some(((3840, 750), Edge::Right))
+        );
+        assert_eq!(on_edge(&manager, (3839, 1500)), Some(Edge::Right));
+    }
+
+    #[test]
+    fn l_shape_inner_corner_is_a_wall() {
+        let manager = l_shape();
+        assert_eq!(cross(&manager, (1919, 500), (1920, 500)), None);
+        assert_eq!(on_edge(&manager, (1919, 500)), None);
+        assert_eq!(cross(&manager, (2500, 1080), (2500, 1079)), None);
+        assert_eq!(on_edge(&manager, (2500, 1080)), None);
+    }
+
+    #[test]
+    fn l_shape_is_entered_on_a_display() {
+        let manager = l_shape();
+        assert_eq!(
+            cross(&manager, (3840, 750), (3839, 750)),
+            Some(((3839, 1500), Edge::Left))
+        );
+        //the mapped position is in the gap of the l, the closest display point is taken instead
+        assert_eq!(
+            cross(&manager, (3840, 100), (3839, 100)),
+            Some(((3839, 1080), Edge::Left))
+        );
+    }
+
+    #[test]
+    fn tall_next_to_short_maps_proportionally() {
+        let manager = tall_next_to_short(Alignment::Proportional);
+        assert_eq!(
+            cross(&manager, (1919, 1080), (1920, 1080)),
+            Some(((1920, 540), Edge::Right))
+        );
+        assert_eq!(
+            cross(&manager, (1920, 1079), (1919, 1079)),
+            Some(((1919, 2158), Edge::Left))
+        );
+    }
+
+    #[test]
+    fn tall_next_to_short_aligned_at_the_top() {
+        let manager = tall_next_to_short(Alignment::Start);
+        assert_eq!(
+            cross(&manager, (1919, 300), (1920, 300)),
+            Some(((1920, 300), Edge::Right))
+        );
+        //below the short display the cursor is moved to its bottom edge
+        assert_eq!(
+            cross(&manager, (1919, 1500), (1920, 1500)),
+            Some(((1920, 1079), Edge::Right))
+        );
+        //the short display has nothing below it
+        assert_eq!(cross(&manager, (2500, 1079), (2500, 1080)), None);
+    }
+
+    #[test]
+    fn edge_links_are_hit_tested() {
+        let mut layout = Layout::default();
+        layout.edge_links.push(EdgeLink {
+            from: EdgeSegment {
+                client: String::from("a"),
+                display: 1,
+                edge: Edge::Right,
+                start: Some(0),
+                end: Some(540),
+            },
+            to: EdgeSegment {
+                client: String::from("b"),
+                display: 1,
+                edge: Edge::Left,
+                start: None,
+                end: None,
+            },
+        });
+        let manager = manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 2160, 1.0)]),
+                client(peer(), "b", vec![display(0, 0, 1920, 1080, 1.0)]),
+            ],
+            layout,
+        );
+
+        assert_eq!(on_edge(&manager, (1919, 270)), Some(Edge::Right));
+        assert_eq!(
+            cross(&manager, (1919, 270), (1920, 270)),
+            Some(((1920, 540), Edge::Right))
+        );
+        //outside the segment the edge is a wall
+        assert_eq!(on_edge(&manager, (1919, 800)), None);
+        assert_eq!(
+            cross(&manager, (1919, 800), (1920, 800)),
+            Some(((1919, 800), Edge::Right))
+        );
+        //the link works in both directions
+        assert_eq!(
+            cross(&manager, (1920, 540), (1919, 540)),
+            Some(((1919, 270), Edge::Left))
+        );
+        //unlinked edges of the peer are walls as well
+        assert_eq!(
+            cross(&manager, (3839, 540), (3840, 540)),
+            Some(((3839, 540), Edge::Right))
+        );
+    }
+
+    #[test]
+    fn rows_wrap_around() {
+        let manager = three_in_line(false, true);
+        assert_eq!(on_edge(&manager, (0, 500)), Some(Edge::Left));
+        assert_eq!(
+            cross(&manager, (0, 500), (-1, 500)),
+            Some(((5759, 500), Edge::Left))
+        );
+        assert_eq!(
+            cross(&manager, (5759, 300), (5760, 300)),
+            Some(((0, 300), Edge::Right))
+        );
+        //the inner edges still lead to the direct neighbour
+        assert_eq!(
+            cross(&manager, (1919, 300), (1920, 300)),
+            Some(((1920, 300), Edge::Right))
+        );
+    }
+
+    #[test]
+    fn columns_wrap_around() {
+        let manager = three_in_line(true, true);
+        assert_eq!(on_edge(&manager, (700, 0)), Some(Edge::Top));
+        assert_eq!(
+            cross(&manager, (700, 0), (700, -1)),
+            Some(((700, 3239), Edge::Top))
+        );
+        assert_eq!(
+            cross(&manager, (900, 3239), (900, 3240)),
+            Some(((900, 0), Edge::Bottom))
+        );
+    }
+
+    #[test]
+    fn outer_edges_are_walls_without_wrap_around() {
+        let row = three_in_line(false, false);
+        assert_eq!(on_edge(&row, (0, 500)), None);
+        assert_eq!(cross(&row, (0, 500), (-1, 500)), None);
+        assert_eq!(cross(&row, (5759, 300), (5760, 300)), None);
+
+        let column = three_in_line(true, false);
+        assert_eq!(on_edge(&column, (700, 0)), None);
+        assert_eq!(cross(&column, (700, 0), (700, -1)), None);
+        assert_eq!(cross(&column, (900, 3239), (900, 3240)), None);
+    }
+
+    #[test]
+    fn dead_zones_block_crossing() {
+        let zone = |client: &str, x, y| DeadZone {
+            client: client.to_string(),
+            display: 1,
+            x,
+            y,
+            width: 20,
+            height: 100,
+        };
+        let manager = manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 1080, 1.0)]),
+                client(peer(), "b", vec![display(0, 0, 1920, 1080, 1.0)]),
+            ],
+            Layout {
+                dead_zones: DeadZones {
+                    corners: 50,
+                    zones: vec![zone("a", 1900, 400), zone("b", 0, 700)],
+                },
+                ..Layout::default()
+            },
+        );
+        let blocked = |y| {
+            on_edge(&manager, (1919, y)).is_none()
+                && cross(&manager, (1919, y), (1920, y)) == Some(((1919, y), Edge::Right))
+        };
+        let crosses = |y| {
+            on_edge(&manager, (1919, y)) == Some(Edge::Right)
+                && cross(&manager, (1919, y), (1920, y)) == Some(((1920, y), Edge::Right))
+        };
+
+        //the corners at both ends of the edge
+        assert!(blocked(0));
+        assert!(blocked(49));
+        assert!(crosses(50));
+        assert!(crosses(1029));
+        assert!(blocked(1030));
+        assert!(blocked(1079));
+        //the rectangle on the edge
+        assert!(crosses(399));
+        assert!(blocked(400));
+        assert!(blocked(499));
+        assert!(crosses(500));
+        //a rectangle of the peer blocks the way back
+        assert_eq!(
+            cross(&manager, (1920, 750), (1919, 750)),
+            Some(((1920, 750), Edge::Left))
+        );
+        assert_eq!(
+            cross(&manager, (1920, 850), (1919, 850)),
+            Some(((1919, 850), Edge::Left))
+        );
+    }
+
+    #[test]
+    fn rotated_displays_are_crossed_in_their_orientation() {
+        //the peer reports the unrotated mode of its portrait display
+        let mut portrait = display(0, 0, 1920, 1080, 1.0);
+        portrait.rotation = 90.0;
+        let manager = manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 1080, 1.0)]),
+                client(peer(), "b", vec![portrait]),
+            ],
+            Layout::default(),
+        );
+        assert_eq!(origins(&manager.clients[1]), vec![(0, 0)]);
+        assert_eq!(manager.clients[1].size(), (1080, 1920));
+
+        assert_eq!(
+            cross(&manager, (1919, 540), (1920, 540)),
+            Some(((1920, 960), Edge::Right))
+        );
+        assert_eq!(
+            cross(&manager, (1920, 1500), (1919, 1500)),
+            Some(((1919, 843), Edge::Left))
+        );
+        //the bottom right corner of the portrait display belongs to the peer
+        let corner = manager
+            .get_local_mouse_position(&MousePosition { x: 2999, y: 1919 })
+            .unwrap();
+        assert_eq!(corner.client, peer());
+        assert_eq!(
+            (corner.mouse_position.x, corner.mouse_position.y),
+            (1079, 1919)
+        );
+    }
+
+    #[test]
+    fn cursors_on_a_removed_display_move_to_the_primary_display() {
+        let mut manager = manager(
+            vec![
+                client(Client::IsSelf, "a", vec![display(0, 0, 1920, 1080, 1.0)]),
+                client(
+                    peer(),
+                    "b",
+                    vec![
+                        display(0, 0, 1920, 1080, 1.0),
+                        display(1920, 0, 1280, 1024, 1.0),
+                    ],
+                ),
+            ],
+            Layout::default(),
+        );
+        let xy = |v: MousePosition| (v.x, v.y);
+        let on_second = ClientMousePosition {
+            client: peer(),
+            mouse_position: MousePosition { x: 2000, y: 100 },
+        };
+        let before = manager.reposition(&on_second).unwrap();
+        assert_eq!(
+            xy(manager
+                .get_local_mouse_position(&before)
+                .unwrap()
+                .mouse_position),
+            (2000, 100)
+        );
+
+        manager.clients[1].displays.pop();
+        manager.update_layout();
+        let center = manager.get_client_center(&peer()).unwrap();
+        assert_eq!(
+            xy(manager.reposition(&on_second).unwrap()),
+            xy(center.clone())
+        );
+        assert_eq!(
+            manager.get_local_mouse_position(&center).unwrap().client,
+            peer()
+        );
+
+        manager.clients.pop();
+        manager.update_layout();
+        assert_eq!(
+            xy(manager.reposition(&on_second).unwrap()),
+            xy(manager.get_client_center(&Client::IsSelf).unwrap())
+        );
+    }
+
+    #[test]
+    fn peers_sort_the_clients_alike() {
+        let addr = |a, b, c, d| SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), 31726);
+        let device = |socket_addr, local_addr| ReceiverDevice {
+            updated: Instant::now(),
+            socket_addr,
+            local_addr: Some(local_addr),
+        };
+        let machine =
+            |id: Client, name, width| client(id, name, vec![display(0, 0, width, 1080, 1.0)]);
+
+        //a reaches b over the lan and c over wifi, b reaches both over the lan
+        let mut a = manager(
+            vec![
+                machine(Client::IsSelf, "a", 1920),
+                machine(Client::IsNetworked(addr(192, 168, 1, 3)), "c", 1280),
+                machine(Client::IsNetworked(addr(10, 0, 0, 2)), "b", 2560),
+            ],
+            Layout::default(),
+        );
+        a.set_own_ip(addr(10, 0, 0, 5));
+        a.filter_clients(&vec![
+            device(addr(10, 0, 0, 2), addr(10, 0, 0, 5)),
+            device(addr(192, 168, 1, 3), addr(192, 168, 1, 9)),
+        ]);
+        let mut b = manager(
+            vec![
+                machine(Client::IsSelf, "b", 2560),
+                machine(Client::IsNetworked(addr(10, 0, 0, 5)), "a", 1920),
+                machine(Client::IsNetworked(addr(10, 0, 0, 3)), "c", 1280),
+            ],
+            Layout::default(),
+        );
+        b.set_own_ip(addr(10, 0, 0, 2));
+
+        let names = |manager: &DisplayManager| -> Vec<Option<String>> {
+            manager.clients.iter().map(|v| v.name.clone()).collect()
+        };
+        assert_eq!(
+            names(&a),
+            vec![
+                Some(String::from("a")),
+                Some(String::from("b")),
+                Some(String::from("c"))
+            ]
+        );
+        assert_eq!(names(&a), names(&b));
+        assert_eq!(a.displays_hash(), b.displays_hash());
+        assert_eq!(a.find_client("192.168.1.9"), Some(Client::IsSelf));
+        assert_eq!(a.find_client("10.0.0.5"), Some(Client::IsSelf));
+    }
+
+    #[test]
+    fn clients_without_a_name_follow_by_address() {
+        let addr = |d| SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, d), 31726);
+        let unnamed = |d| {
+            let mut v = client(
+                Client::IsNetworked(addr(d)),
+                "",
+                vec![display(0, 0, 1920, 1080, 1.0)],
+            );
+            v.name = None;
+            v
+        };
+        let mut manager = manager(
+            vec![
+                unnamed(7),
+                client(peer(), "z", vec![display(0, 0, 1920, 1080, 1.0)]),
+                unnamed(3),
+            ],
+            Layout::default(),
+        );
+        manager.sort_client_displays();
+
+        let clients: Vec<Client> = manager.clients.iter().map(|v| v.client.clone()).collect();
+        assert_eq!(
+            clients,
+            vec![
+                peer(),
+                Client::IsNetworked(addr(3)),
+                Client::IsNetworked(addr(7))
+            ]
+        );
+    }
+}
@@ -0,0 +1,617 @@
+use {
+    async_trait::async_trait,
+    serde::{Deserialize, Serialize},
+    std::{
+        error, fmt,
+        io::SeekFrom,
+        net::{Ipv4Addr, SocketAddrV4},
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+    tokio::{
+        fs::{self, File, OpenOptions},
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+    },
+};
+
+type Result<T> = std::result::Result<T, FileTransferError>;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum FileTransferError {
+    IoError(std::io::Error),
+    SerdeError(serde_json::error::Error),
+    InvalidFileName,
+    InvalidHeader,
+    ChunkChecksumMismatch,
+    ChecksumMismatch,
+    Rejected(String),
+    UnknownPeer,
+    TooLarge(u64),
+    Busy,
+}
+
+impl error::Error for FileTransferError {}
+
+impl fmt::Display for FileTransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileTransferError::IoError(e) => write!(f, "File transfer IO Error: {}", e),
+            FileTransferError::SerdeError(e) => write!(f, "File transfer header error: {}", e),
+            FileTransferError::InvalidFileName => write!(f, "File name is invalid"),
+            FileTransferError::InvalidHeader => write!(f, "Received an invalid transfer header"),
+            FileTransferError::ChunkChecksumMismatch => {
+                write!(f, "A chunk was corrupted during the transfer")
+            }
+            FileTransferError::ChecksumMismatch => {
+                write!(f, "The received file does not match its checksum")
+            }
+            FileTransferError::Rejected(reason) => {
+                write!(f, "The receiver rejected the file: {}", reason)
+            }
+            FileTransferError::UnknownPeer => write!(f, "The sender is not a known peer"),
+            FileTransferError::TooLarge(size) => {
+                write!(f, "The file is larger than the limit of {} bytes", size)
+            }
+            FileTransferError::Busy => write!(f, "The same file is already being received"),
+        }
+    }
+}
+
+impl From<std::io::Error> for FileTransferError {
+    fn from(value: std::io::Error) -> Self {
+        FileTransferError::IoError(value)
+    }
+}
+
+impl From<serde_json::error::Error> for FileTransferError {
+    fn from(value: serde_json::error::Error) -> Self {
+        FileTransferError::SerdeError(value)
+    }
+}
+
+/// Sent as the first line of every transfer. The receiver answers with the amount of bytes it already has.
+#[derive(Serialize, Deserialize, Debug)]
+struct FileHeader {
+    name: String,
+    size: u64,
+    checksum: u32,
+}
+
+/// Decides which machines may send files to this one
+#[async_trait]
+pub trait KnownPeers
+where
+    Self: Sync + Send,
+{
+    async fn is_peer(&self, ip: &Ipv4Addr) -> bool;
+}
+
+pub struct FileTransfer {
+    port: u16,
+    receive_directory: PathBuf,
+    max_file_size: u64,
+    peers: Arc<dyn KnownPeers>,
+    /// The part files that are currently written to
+    active: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl FileTransfer {
+    pub fn new(
+        port: u16,
+        receive_directory: PathBuf,
+        max_file_size: u64,
+        peers: Arc<dyn KnownPeers>,
+    ) -> Self {
+        FileTransfer {
+            port,
+            receive_directory,
+            max_file_size,
+            peers,
+            active: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub async fn listen(&self) -> Result<()> {
+        let listener =
+            TcpListener::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, self.port)).await?;
+        loop {
+            let (mut stream, src) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Error accepting file transfer: {}", e);
+                    continue;
+                }
+            };
+            let src_ip = match src {
+                std::net::SocketAddr::V4(v) if self.peers.is_peer(v.ip()).await => *v.ip(),
+                _ => {
+                    println!(
+                        "Refused file transfer from {}: {}",
+                        src,
+                        FileTransferError::UnknownPeer
+                    );
+                    let _ = stream.write_all(b"ERR unknown peer\n").await;
+                    continue;
+                }
+            };
+            let receive = Receive {
+                src: src_ip,
+                receive_directory: self.receive_directory.clone(),
+                max_file_size: self.max_file_size,
+                active: self.active.clone(),
+            };
+            tokio::spawn(async move {
+                match receive.run(stream).await {
+                    Ok(path) => println!("Received file from {}: {}", src, path.display()),
+                    Err(e) => println!("Error receiving file from {}: {}", src, e),
+                }
+            });
+        }
+    }
+
+    pub async fn send_file(&self, target: Ipv4Addr, path: &Path) -> Result<()> {
+        let name = match path.file_name().and_then(|v| v.to_str()) {
+            Some(v) => v.to_string(),
+            None => return Err(FileTransferError::InvalidFileName),
+        };
+        let size = fs::metadata(path).await?.len();
+        let checksum = file_checksum(path, size).await?;
+
+        let stream = TcpStream::connect(SocketAddrV4::new(target, self.port)).await?;
+        let mut stream = BufReader::new(stream);
+        let header = serde_json::to_string(&FileHeader {
+            name: name.clone(),
+            size,
+            checksum,
+        })?;
+        stream
+            .get_mut()
+            .write_all((header + "\n").as_bytes())
+            .await?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let offset = match line.trim().parse::<u64>() {
+            Ok(v) if v <= size => v,
+            _ => return Err(FileTransferError::Rejected(line.trim().to_string())),
+        };
+        if offset > 0 {
+            println!("Resuming transfer of {} at byte {}", name, offset);
+        }
+
+        let mut file = File::open(path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let mut progress = Progress::new(name, size, offset);
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let amount = file.read(&mut buf).await?;
+            if amount == 0 {
+                break;
+            }
+            let chunk = &buf[..amount];
+            let stream = stream.get_mut();
+            stream.write_all(&(amount as u32).to_be_bytes()).await?;
+            stream.write_all(&crc32(chunk).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+            progress.advance(amount as u64);
+        }
+        stream.get_mut().write_all(&0u32.to_be_bytes()).await?;
+
+        line.clear();
+        stream.read_line(&mut line).await?;
+        match line.trim() {
+            "OK" => Ok(()),
+            v => Err(FileTransferError::Rejected(v.to_string())),
+        }
+    }
+}
+
+/// One incoming transfer
+struct Receive {
+    src: Ipv4Addr,
+    receive_directory: PathBuf,
+    max_file_size: u64,
+    active: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl Receive {
+    async fn run(self, stream: TcpStream) -> Result<PathBuf> {
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        let header = serde_json::from_str::<FileHeader>(line.trim())?;
+        let name = match Path::new(&header.name).file_name() {
+            Some(v) => v.to_string_lossy().to_string(),
+            None => return Err(FileTransferError::InvalidFileName),
+        };
+        if header.size > self.max_file_size {
+            stream.get_mut().write_all(b"ERR too large\n").await?;
+            return Err(FileTransferError::TooLarge(self.max_file_size));
+        }
+
+        let part_path = part_path(&self.receive_directory, &name, &self.src, header.checksum);
+        {
+            let mut active = self.active.lock().await;
+            if active.contains(&part_path) {
+                stream.get_mut().write_all(b"ERR busy\n").await?;
+                return Err(FileTransferError::Busy);
+            }
+            active.push(part_path.clone());
+        }
+        let result = self.receive(&mut stream, &header, &name, &part_path).await;
+        self.active.lock().await.retain(|v| v != &part_path);
+        result
+    }
+
+    async fn receive(
+        &self,
+        stream: &mut BufReader<TcpStream>,
+        header: &FileHeader,
+        name: &str,
+        part_path: &Path,
+    ) -> Result<PathBuf> {
+        fs::create_dir_all(&self.receive_directory).await?;
+        let mut part = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(part_path)
+            .await?;
+        let mut offset = part.metadata().await?.len();
+        if offset > header.size {
+            part.set_len(0).await?;
+            offset = 0;
+        }
+        stream
+            .get_mut()
+            .write_all(format!("{}\n", offset).as_bytes())
+            .await?;
+
+        let received = Receive::receive_chunks(stream, &mut part, header, name, offset).await;
+        //an interrupted transfer keeps everything it received, a resumed one continues from there
+        part.flush().await?;
+        drop(part);
+        let offset = received?;
+
+        if offset != header.size || file_checksum(part_path, offset).await? != header.checksum {
+            fs::remove_file(part_path).await?;
+            stream.get_mut().write_all(b"ERR checksum\n").await?;
+            return Err(FileTransferError::ChecksumMismatch);
+        }
+
+        //picking the name and renaming happen under the lock, so two files never take the same name
+        let destination = {
+            let _active = self.active.lock().await;
+            let destination = free_destination(&self.receive_directory, name).await;
+            fs::rename(part_path, &destination).await?;
+            destination
+        };
+        stream.get_mut().write_all(b"OK\n").await?;
+        Ok(destination)
+    }
+
+    /// Appends the chunks to `part` until the end marker and returns the length of the file
+    async fn receive_chunks(
+        stream: &mut BufReader<TcpStream>,
+        part: &mut File,
+        header: &FileHeader,
+        name: &str,
+        mut offset: u64,
+    ) -> Result<u64> {
+        let mut progress = Progress::new(name.to_string(), header.size, offset);
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let amount = stream.read_u32().await? as usize;
+            if amount == 0 {
+                return Ok(offset);
+            }
+            if amount > CHUNK_SIZE || offset + amount as u64 > header.size {
+                return Err(FileTransferError::InvalidHeader);
+            }
+            let checksum = stream.read_u32().await?;
+            let chunk = &mut buf[..amount];
+            stream.read_exact(chunk).await?;
+            if crc32(chunk) != checksum {
+                stream.get_mut().write_all(b"ERR chunk checksum\n").await?;
+                return Err(FileTransferError::ChunkChecksumMismatch);
+            }
+            part.write_all(chunk).await?;
+            offset += amount as u64;
+            progress.advance(amount as u64);
+        }
+    }
+}
+
+/// Unfinished files are kept per sender and content, so a resumed transfer finds its part again
+fn part_path(directory: &Path, name: &str, src: &Ipv4Addr, checksum: u32) -> PathBuf {
+    directory.join(format!(".{}.{}.{:08x}.part", name, src, checksum))
+}
+
+async fn free_destination(directory: &Path, name: &str) -> PathBuf {
+    let mut destination = directory.join(name);
+    let mut index = 1;
+    while fs::metadata(&destination).await.is_ok() {
+        destination = directory.join(format!("{} ({})", name, index));
+        index += 1;
+    }
+    destination
+}
+
+async fn file_checksum(path: &Path, size: u64) -> Result<u32> {
+    let mut file = File::open(path).await?.take(size);
+    let mut crc = Crc32::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let amount = file.read(&mut buf).await?;
+        if amount == 0 {
+            break;
+        }
+        crc.update(&buf[..amount]);
+    }
+    Ok(crc.finish())
+}
+
+struct Progress {
+    name: String,
+    total: u64,
+    done: u64,
+    last_reported: u64,
+}
+
+impl Progress {
+    pub fn new(name: String, total: u64, done: u64) -> Self {
+        Progress {
+            name,
+            total,
+            done,
+            last_reported: 0,
+        }
+    }
+
+    pub fn advance(&mut self, amount: u64) {
+        self.done += amount;
+        let percent = match self.total {
+            0 => 100,
+            v => self.done * 100 / v,
+        };
+        if percent >= self.last_reported + 10 || (percent == 100 && self.last_reported != 100) {
+            self.last_reported = percent;
+            println!(
+                "Transfer {}: {}% ({}/{} bytes)",
+                self.name, percent, self.done, self.total
+            );
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                0xEDB88320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+struct Crc32 {
+    value: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32 { value: 0xFFFFFFFF }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.value =
+                CRC32_TABLE[((self.value ^ *byte as u32) & 0xFF) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.value ^ 0xFFFFFFFF
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Peers(Vec<Ipv4Addr>);
+
+    #[async_trait]
+    impl KnownPeers for Peers {
+        async fn is_peer(&self, ip: &Ipv4Addr) -> bool {
+            self.0.contains(ip)
+        }
+    }
+
+    fn temp_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("seamless-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    /// Starts a receiver on `port` and returns a sender for it together with the receive directory
+    async fn transfer(
+        port: u16,
+        max_file_size: u64,
+        peers: Vec<Ipv4Addr>,
+    ) -> (FileTransfer, PathBuf) {
+        let directory = temp_directory();
+        let receiver = FileTransfer::new(
+            port,
+            directory.clone(),
+            max_file_size,
+            Arc::new(Peers(peers)),
+        );
+        tokio::spawn(async move { receiver.listen().await });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let sender = FileTransfer::new(
+            port,
+            temp_directory(),
+            max_file_size,
+            Arc::new(Peers(Vec::new())),
+        );
+        (sender, directory)
+    }
+
+    async fn source_file(content: &[u8]) -> PathBuf {
+        let directory = temp_directory();
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("data.bin");
+        fs::write(&path, content).await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn known_peers_can_send_files() {
+        let content: Vec<u8> = (0..200_000u32).map(|v| v as u8).collect();
+        let source = source_file(&content).await;
+        let (sender, directory) = transfer(47811, 1024 * 1024, vec![Ipv4Addr::LOCALHOST]).await;
+
+        sender
+            .send_file(Ipv4Addr::LOCALHOST, &source)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(directory.join("data.bin")).await.unwrap(), content);
+        sender
+            .send_file(Ipv4Addr::LOCALHOST, &source)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read(directory.join("data.bin (1)")).await.unwrap(),
+            content
+        );
+    }
+
+    /// Sends the header for `source` and the first `chunks` chunks, then drops the connection.
+    /// Returns the offset the receiver asked for.
+    async fn interrupted_transfer(port: u16, source: &Path, chunks: usize) -> u64 {
+        let content = fs::read(source).await.unwrap();
+        let header = serde_json::to_string(&FileHeader {
+            name: String::from("data.bin"),
+            size: content.len() as u64,
+            checksum: crc32(&content),
+        })
+        .unwrap();
+        let stream = TcpStream::connect(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+            .await
+            .unwrap();
+        let mut stream = BufReader::new(stream);
+        stream
+            .get_mut()
+            .write_all((header + "\n").as_bytes())
+            .await
+            .unwrap();
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let offset: u64 = line.trim().parse().unwrap();
+
+        for chunk in content[offset as usize..].chunks(CHUNK_SIZE).take(chunks) {
+            let stream = stream.get_mut();
+            stream
+                .write_all(&(chunk.len() as u32).to_be_bytes())
+                .await
+                .unwrap();
+            stream.write_all(&crc32(chunk).to_be_bytes()).await.unwrap();
+            stream.write_all(chunk).await.unwrap();
+        }
+        drop(stream);
+        //the receiver notices the closed connection and releases the part file
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        offset
+    }
+
+    #[tokio::test]
+    async fn interrupted_transfers_resume_from_the_part_file() {
+        let content: Vec<u8> = (0..200_000u32).map(|v| (v * 7) as u8).collect();
+        let source = source_file(&content).await;
+        let (sender, directory) = transfer(47814, 1024 * 1024, vec![Ipv4Addr::LOCALHOST]).await;
+
+        assert_eq!(interrupted_transfer(47814, &source, 1).await, 0);
+        let part = part_path(
+            &directory,
+            "data.bin",
+            &Ipv4Addr::LOCALHOST,
+            crc32(&content),
+        );
+        assert_eq!(fs::metadata(&part).await.unwrap().len(), CHUNK_SIZE as u64);
+        assert_eq!(
+            interrupted_transfer(47814, &source, 1).await,
+            CHUNK_SIZE as u64
+        );
+
+        sender
+            .send_file(Ipv4Addr::LOCALHOST, &source)
+            .await
+            .unwrap();
+        assert_eq!(fs::read(directory.join("data.bin")).await.unwrap(), content);
+        assert!(fs::metadata(&part).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_hosts_are_refused() {
+        let source = source_file(b"secret").await;
+        let (sender, directory) = transfer(47812, 1024, Vec::new()).await;
+
+        let result = sender.send_file(Ipv4Addr::LOCALHOST, &source).await;
+        assert!(matches!(result, Err(FileTransferError::Rejected(_))));
+        assert!(fs::metadata(directory).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn files_above_the_limit_are_refused() {
+        let source = source_file(&[0; 2048]).await;
+        let (sender, directory) = transfer(47813, 1024, vec![Ipv4Addr::LOCALHOST]).await;
+
+        let result = sender.send_file(Ipv4Addr::LOCALHOST, &source).await;
+        assert!(matches!(result, Err(FileTransferError::Rejected(_))));
+        assert!(fs::metadata(directory).await.is_err());
+    }
+
+    #[test]
+    fn part_files_are_kept_per_sender_and_content() {
+        let directory = Path::new("/tmp");
+        let a = Ipv4Addr::new(10, 0, 0, 1);
+        let b = Ipv4Addr::new(10, 0, 0, 2);
+        assert_eq!(
+            part_path(directory, "f", &a, 1),
+            part_path(directory, "f", &a, 1)
+        );
+        assert_ne!(
+            part_path(directory, "f", &a, 1),
+            part_path(directory, "f", &b, 1)
+        );
+        assert_ne!(
+            part_path(directory, "f", &a, 1),
+            part_path(directory, "f", &a, 2)
+        );
+    }
+
+    #[test]
+    fn crc32_matches_the_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
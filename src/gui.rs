@@ -1,16 +1,33 @@
 use {
+    crate::{
+        control::{self, ControlCommand},
+        display::{Alignment, Edge, Snapshot},
+        input::MousePosition,
+    },
     eframe::{
-        egui::{self, CursorIcon, ViewportBuilder},
+        egui::{self, CursorIcon},
         Frame,
     },
     std::{
-        borrow::BorrowMut,
-        option,
         process::{Child, Command},
+        sync::mpsc as std_mpsc,
+        thread,
+        time::{Duration, Instant},
     },
     tokio::sync::mpsc::{self, error::SendError},
 };
 
+/// How often the settings window fetches the layout
+const SETTINGS_REFRESH: Duration = Duration::from_secs(1);
+
+/// How long a peer shows its number during calibration
+const IDENTIFY_DURATION: Duration = Duration::from_secs(30);
+
+/// How long the drop window waits for a drag that left it
+const DROP_TIMEOUT: Duration = Duration::from_secs(3);
+
+const DROP_SIZE: f32 = 160.0;
+
 pub struct GUI {}
 
 impl GUI {
@@ -24,11 +41,434 @@ impl GUI {
             .with_inner_size(egui::vec2(320.0, 240.0));
         let ui = SeamlessUI::new();
 
-        eframe::run_native("Seamless", options, Box::new(|_cc| Box::new(ui)))
+        eframe::run_native("Seamless", options, Box::new(|_cc| Ok(Box::new(ui))))
+            .expect("Was unable to create window. Panic! 🚨");
+
+        GUI {}
+    }
+
+    /// Shows `number` in large digits until `IDENTIFY_DURATION` passed
+    pub fn identify(number: u32) -> Self {
+        let mut options = eframe::NativeOptions::default();
+        options.viewport = options
+            .viewport
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_inner_size(egui::vec2(320.0, 320.0));
+        let ui = IdentifyUI {
+            number,
+            started: Instant::now(),
+        };
+
+        eframe::run_native("Seamless", options, Box::new(|_cc| Ok(Box::new(ui))))
             .expect("Was unable to create window. Panic! 🚨");
 
         GUI {}
     }
+
+    /// Opens a small window centered on `x`, `y` and sends the files dropped into it to the
+    /// peer beyond the edge the drag is held at
+    pub fn catch_drop(x: i32, y: i32, control_port: u16) -> Self {
+        let mut options = eframe::NativeOptions::default();
+        options.viewport = options
+            .viewport
+            .with_decorations(false)
+            .with_always_on_top()
+            .with_drag_and_drop(true)
+            .with_inner_size(egui::vec2(DROP_SIZE, DROP_SIZE))
+            .with_position(egui::pos2(
+                x as f32 - DROP_SIZE / 2.0,
+                y as f32 - DROP_SIZE / 2.0,
+            ));
+        let ui = DropUI {
+            control_port,
+            last_hovered: Instant::now(),
+        };
+
+        eframe::run_native("Seamless drop", options, Box::new(|_cc| Ok(Box::new(ui))))
+            .expect("Was unable to create window. Panic! 🚨");
+
+        GUI {}
+    }
+
+    /// Opens the layout editor, which talks to the running instance on `control_port`
+    pub fn settings(control_port: u16) -> Self {
+        let mut options = eframe::NativeOptions::default();
+        options.viewport = options.viewport.with_inner_size(egui::vec2(800.0, 600.0));
+        eframe::run_native(
+            "Seamless settings",
+            options,
+            Box::new(move |cc| Ok(Box::new(SettingsUI::new(control_port, cc.egui_ctx.clone())))),
+        )
+        .expect("Was unable to create window. Panic! 🚨");
+
+        GUI {}
+    }
+}
+
+/// What the settings worker reports back to the window
+enum SettingsUpdate {
+    Snapshot(Snapshot),
+    Status(String),
+}
+
+struct SettingsUI {
+    /// Commands for the worker, which talks to the running instance so a slow instance never
+    /// blocks the window
+    commands: std_mpsc::Sender<ControlCommand>,
+    updates: std_mpsc::Receiver<SettingsUpdate>,
+    snapshot: Option<Snapshot>,
+    status: String,
+    /// The index of the dragged client and how far it was moved on screen
+    dragging: Option<(usize, egui::Vec2)>,
+    link_from: String,
+    link_to: String,
+    alignment: Alignment,
+}
+
+impl SettingsUI {
+    fn new(control_port: u16, ctx: egui::Context) -> Self {
+        let (commands, command_receiver) = std_mpsc::channel();
+        let (update_sender, updates) = std_mpsc::channel();
+        thread::spawn(move || {
+            SettingsUI::worker(control_port, command_receiver, update_sender, ctx)
+        });
+        SettingsUI {
+            commands,
+            updates,
+            snapshot: None,
+            status: String::new(),
+            dragging: None,
+            link_from: String::new(),
+            link_to: String::new(),
+            alignment: Alignment::Proportional,
+        }
+    }
+
+    /// Fetches the layout every `SETTINGS_REFRESH` and right after each command of the window,
+    /// until the window is closed
+    fn worker(
+        control_port: u16,
+        commands: std_mpsc::Receiver<ControlCommand>,
+        updates: std_mpsc::Sender<SettingsUpdate>,
+        ctx: egui::Context,
+    ) {
+        loop {
+            let update =
+                match control::send_command_blocking(control_port, &ControlCommand::Snapshot) {
+                    Ok(v) => match serde_json::from_str::<Snapshot>(&v) {
+                        Ok(snapshot) => SettingsUpdate::Snapshot(snapshot),
+                        Err(_) => SettingsUpdate::Status(v),
+                    },
+                    Err(e) => SettingsUpdate::Status(e.to_string()),
+                };
+            if updates.send(update).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+
+            match commands.recv_timeout(SETTINGS_REFRESH) {
+                Ok(command) => {
+                    let status = match control::send_command_blocking(control_port, &command) {
+                        Ok(v) => v,
+                        Err(e) => e.to_string(),
+                    };
+                    if updates.send(SettingsUpdate::Status(status)).is_err() {
+                        return;
+                    }
+                }
+                Err(std_mpsc::RecvTimeoutError::Timeout) => {}
+                Err(std_mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn run(&mut self, command: ControlCommand) {
+        if self.commands.send(command).is_err() {
+            self.status = String::from("The settings worker stopped");
+        }
+    }
+
+    /// Places the client dropped with its center at `center` next to the closest other client
+    fn dropped(&mut self, index: usize, center: egui::Pos2) {
+        let snapshot = match &self.snapshot {
+            Some(v) => v,
+            None => return,
+        };
+        let closest = snapshot
+            .clients
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, v)| {
+                let other_center = egui::pos2(
+                    v.x as f32 + v.width as f32 / 2.0,
+                    v.y as f32 + v.height as f32 / 2.0,
+                );
+                (v, center - other_center)
+            })
+            .min_by(|a, b| a.1.length().total_cmp(&b.1.length()));
+        let (neighbour, offset) = match closest {
+            Some(v) => v,
+            None => return,
+        };
+        let edge = match (
+            offset.x.abs() >= offset.y.abs(),
+            offset.x < 0.0,
+            offset.y < 0.0,
+        ) {
+            (true, true, _) => Edge::Left,
+            (true, false, _) => Edge::Right,
+            (false, _, true) => Edge::Top,
+            (false, _, false) => Edge::Bottom,
+        };
+        match (&snapshot.clients[index].name, &neighbour.name) {
+            (Some(client), Some(neighbour)) => {
+                let command = ControlCommand::Place(client.clone(), edge, neighbour.clone());
+                self.run(command);
+            }
+            _ => self.status = String::from("Clients without a name can not be placed"),
+        }
+    }
+
+    fn lock_toggle(&mut self, ui: &mut egui::Ui) {
+        let mut locked = match &self.snapshot {
+            Some(v) => v.locked,
+            None => return,
+        };
+        if ui
+            .checkbox(&mut locked, "Lock the cursor to the current machine")
+            .changed()
+        {
+            self.run(ControlCommand::ToggleLock);
+        }
+    }
+
+    fn alignment_editor(&mut self, ui: &mut egui::Ui) {
+        let names: Vec<String> = match &self.snapshot {
+            Some(v) => v.clients.iter().filter_map(|v| v.name.clone()).collect(),
+            None => Vec::new(),
+        };
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("to")
+                .selected_text(self.link_from.as_str())
+                .show_ui(ui, |ui| {
+                    for name in names.iter() {
+                        ui.selectable_value(&mut self.link_from, name.clone(), name.as_str());
+                    }
+                });
+            egui::ComboBox::from_label("aligned")
+                .selected_text(self.link_to.as_str())
+                .show_ui(ui, |ui| {
+                    for name in names.iter() {
+                        ui.selectable_value(&mut self.link_to, name.clone(), name.as_str());
+                    }
+                });
+            let offset = match self.alignment {
+                Alignment::Offset(v) => v,
+                _ => 0,
+            };
+            egui::ComboBox::from_id_source("alignment")
+                .selected_text(format!("{:?}", self.alignment))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.alignment,
+                        Alignment::Proportional,
+                        "Proportional",
+                    );
+                    ui.selectable_value(&mut self.alignment, Alignment::Start, "Start");
+                    ui.selectable_value(&mut self.alignment, Alignment::Center, "Center");
+                    ui.selectable_value(&mut self.alignment, Alignment::Offset(offset), "Offset");
+                });
+            if let Alignment::Offset(ref mut v) = self.alignment {
+                ui.add(egui::DragValue::new(v));
+            }
+            if ui.button("Apply").clicked() {
+                let command = ControlCommand::SetAlignment(
+                    self.link_from.clone(),
+                    self.link_to.clone(),
+                    self.alignment,
+                );
+                self.run(command);
+            }
+        });
+    }
+
+    fn layout_view(&mut self, ui: &mut egui::Ui) {
+        let snapshot = match &self.snapshot {
+            Some(v) => v.clone(),
+            None => {
+                ui.label("Waiting for the running instance…");
+                return;
+            }
+        };
+        let area = ui.available_rect_before_wrap();
+        let left = snapshot.clients.iter().map(|v| v.x).min().unwrap_or(0) as f32;
+        let top = snapshot.clients.iter().map(|v| v.y).min().unwrap_or(0) as f32;
+        let right = snapshot
+            .clients
+            .iter()
+            .map(|v| v.x + v.width as i32)
+            .max()
+            .unwrap_or(1) as f32;
+        let bottom = snapshot
+            .clients
+            .iter()
+            .map(|v| v.y + v.height as i32)
+            .max()
+            .unwrap_or(1) as f32;
+        let scale = (area.width() / (right - left).max(1.0))
+            .min(area.height() / (bottom - top).max(1.0))
+            * 0.9;
+        let to_screen = |x: i32, y: i32| {
+            area.min + egui::vec2((x as f32 - left) * scale, (y as f32 - top) * scale)
+        };
+        let painter = ui.painter_at(area);
+
+        for (index, client) in snapshot.clients.iter().enumerate() {
+            let moved = match self.dragging {
+                Some((dragged, offset)) if dragged == index => offset,
+                _ => egui::Vec2::ZERO,
+            };
+            let rect = egui::Rect::from_min_max(
+                to_screen(client.x, client.y),
+                to_screen(
+                    client.x + client.width as i32,
+                    client.y + client.height as i32,
+                ),
+            )
+            .translate(moved);
+            for display in client.displays.iter() {
+                let display_rect = egui::Rect::from_min_max(
+                    to_screen(display.x, display.y),
+                    to_screen(
+                        display.x + display.width as i32,
+                        display.y + display.height as i32,
+                    ),
+                )
+                .translate(moved);
+                painter.rect_filled(display_rect, 2.0, egui::Color32::from_gray(70));
+                painter.text(
+                    display_rect.left_top() + egui::vec2(4.0, 4.0),
+                    egui::Align2::LEFT_TOP,
+                    display.id.to_string(),
+                    egui::FontId::proportional(12.0),
+                    egui::Color32::GRAY,
+                );
+            }
+            painter.rect_stroke(rect, 2.0, egui::Stroke::new(2.0, egui::Color32::LIGHT_BLUE));
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                client.name.clone().unwrap_or_default(),
+                egui::FontId::proportional(16.0),
+                egui::Color32::WHITE,
+            );
+
+            let response = ui.interact(rect, egui::Id::new(("client", index)), egui::Sense::drag());
+            if response.drag_started() {
+                self.dragging = Some((index, egui::Vec2::ZERO));
+            }
+            if response.dragged() {
+                if let Some((_, ref mut offset)) = self.dragging {
+                    *offset += response.drag_delta();
+                }
+            }
+            if response.drag_stopped() {
+                let center = rect.center() - area.min;
+                self.dragging = None;
+                self.dropped(
+                    index,
+                    egui::pos2(center.x / scale + left, center.y / scale + top),
+                );
+            }
+        }
+    }
+}
+
+impl eframe::App for SettingsUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        // a new snapshot could reorder the clients under the dragged one
+        if self.dragging.is_none() {
+            while let Ok(update) = self.updates.try_recv() {
+                match update {
+                    SettingsUpdate::Snapshot(v) => self.snapshot = Some(v),
+                    SettingsUpdate::Status(v) => self.status = v,
+                }
+            }
+        }
+
+        egui::TopBottomPanel::bottom("settings").show(ctx, |ui| {
+            self.lock_toggle(ui);
+            self.alignment_editor(ui);
+            ui.label(self.status.as_str());
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label("Drag a machine next to another one to place it there. Its displays keep the arrangement of its own display settings.");
+            self.layout_view(ui);
+        });
+        ctx.request_repaint_after(SETTINGS_REFRESH);
+    }
+}
+
+struct IdentifyUI {
+    number: u32,
+    started: Instant,
+}
+
+impl eframe::App for IdentifyUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        if self.started.elapsed() >= IDENTIFY_DURATION {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.centered_and_justified(|ui| {
+                ui.label(egui::RichText::new(self.number.to_string()).size(200.0));
+            });
+        });
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+}
+
+struct DropUI {
+    control_port: u16,
+    /// When a drag was last over the window
+    last_hovered: Instant,
+}
+
+impl eframe::App for DropUI {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        let (hovered, dropped) = ctx.input(|i| {
+            (
+                !i.raw.hovered_files.is_empty(),
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|v| v.path.clone())
+                    .collect::<Vec<_>>(),
+            )
+        });
+        if hovered {
+            self.last_hovered = Instant::now();
+        }
+        if !dropped.is_empty() {
+            if let Err(e) = control::send_command_blocking(
+                self.control_port,
+                &ControlCommand::DropFiles(dropped),
+            ) {
+                println!("Was unable to send the dropped files: {}", e);
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else if self.last_hovered.elapsed() >= DROP_TIMEOUT {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.centered_and_justified(|ui| {
+                ui.label("Drop to send");
+            });
+        });
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
 }
 
 struct SeamlessUI {}
@@ -48,34 +488,56 @@ impl eframe::App for SeamlessUI {
     }
 }
 
+pub enum GUIMessage {
+    Init,
+    Quit,
+    Identify(u32),
+    /// Opens the drop window at the position, in logical pixels of this machine
+    CatchDrop(MousePosition),
+}
+
 pub struct GUIHandler {
-    sender: mpsc::UnboundedSender<bool>,
+    sender: mpsc::UnboundedSender<GUIMessage>,
 }
 
 impl GUIHandler {
-    pub fn quit_ui(&self) -> Result<(), SendError<bool>> {
-        self.sender.send(false)?;
+    pub fn quit_ui(&self) -> Result<(), SendError<GUIMessage>> {
+        self.sender.send(GUIMessage::Quit)?;
+        Ok(())
+    }
+
+    pub fn init_ui(&self) -> Result<(), SendError<GUIMessage>> {
+        self.sender.send(GUIMessage::Init)?;
         Ok(())
     }
 
-    pub fn init_ui(&self) -> Result<(), SendError<bool>> {
-        self.sender.send(true)?;
+    pub fn identify(&self, number: u32) -> Result<(), SendError<GUIMessage>> {
+        self.sender.send(GUIMessage::Identify(number))?;
+        Ok(())
+    }
+
+    pub fn catch_drop(&self, position: MousePosition) -> Result<(), SendError<GUIMessage>> {
+        self.sender.send(GUIMessage::CatchDrop(position))?;
         Ok(())
     }
 }
 
 pub struct GUIProcessManager {
     gui_process: Option<Child>,
+    identify_process: Option<Child>,
+    drop_process: Option<Child>,
     own_path: String,
-    receiver: mpsc::UnboundedReceiver<bool>,
+    receiver: mpsc::UnboundedReceiver<GUIMessage>,
 }
 
 impl GUIProcessManager {
     pub fn new(own_path: String) -> (Self, GUIHandler) {
-        let (sender, receiver) = mpsc::unbounded_channel::<bool>();
+        let (sender, receiver) = mpsc::unbounded_channel::<GUIMessage>();
         (
             GUIProcessManager {
                 gui_process: None,
+                identify_process: None,
+                drop_process: None,
                 own_path,
                 receiver,
             },
@@ -106,22 +568,67 @@ impl GUIProcessManager {
         Ok(())
     }
 
+    /// Shows the calibration number, replacing a number that is still shown
+    fn identify(&mut self, number: u32) -> Result<(), std::io::Error> {
+        if let Some(mut v) = self.identify_process.take() {
+            let _ = v.kill(); //the number may already be gone
+            v.wait()?;
+        }
+        self.identify_process = Some(
+            Command::new(&self.own_path)
+                .arg("gui")
+                .arg("--identify")
+                .arg(number.to_string())
+                .spawn()?,
+        );
+        Ok(())
+    }
+
+    /// Opens the drop window, replacing one that is still open from an earlier drag
+    fn catch_drop(&mut self, position: MousePosition) -> Result<(), std::io::Error> {
+        if let Some(mut v) = self.drop_process.take() {
+            let _ = v.kill(); //the window closes itself after a drop
+            v.wait()?;
+        }
+        self.drop_process = Some(
+            Command::new(&self.own_path)
+                .arg("gui")
+                .arg("--drop")
+                .arg(position.x.to_string())
+                .arg(position.y.to_string())
+                .spawn()?,
+        );
+        Ok(())
+    }
+
     pub async fn listen(&mut self) {
         loop {
             match self.receiver.recv().await {
-                Some(true) => match self.init_ui() {
+                Some(GUIMessage::Init) => match self.init_ui() {
                     Err(e) => {
                         println!("Was unable to init ui: {}", e)
                     }
                     _ => {}
                 },
-                Some(false) => match self.quit_ui() {
+                Some(GUIMessage::Quit) => match self.quit_ui() {
                     Err(e) => {
                         println!("Was unable to quit ui: {}", e)
                     }
 
                     _ => {}
                 },
+                Some(GUIMessage::Identify(number)) => match self.identify(number) {
+                    Err(e) => {
+                        println!("Was unable to show calibration number: {}", e)
+                    }
+                    _ => {}
+                },
+                Some(GUIMessage::CatchDrop(position)) => match self.catch_drop(position) {
+                    Err(e) => {
+                        println!("Was unable to open the drop window: {}", e)
+                    }
+                    _ => {}
+                },
                 None => {
                     println!("Received nothing? What how?")
                 }
@@ -0,0 +1,100 @@
+use {
+    crate::input::Key,
+    serde::{Deserialize, Serialize},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum HotkeyAction {
+    /// Jumps to the center of the primary display of the peer with this name or ip
+    SwitchTo(String),
+    Next,
+    Previous,
+    Home,
+    /// Keeps the cursor on the current machine until toggled again
+    ToggleLock,
+}
+
+/// Triggers `action` when exactly `keys` are held. The keys are never forwarded to a target.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hotkey {
+    pub keys: Vec<Key>,
+    pub action: HotkeyAction,
+}
+
+pub struct Hotkeys {
+    hotkeys: Vec<Hotkey>,
+}
+
+impl Hotkeys {
+    pub fn new(hotkeys: Vec<Hotkey>) -> Self {
+        Hotkeys { hotkeys }
+    }
+
+    pub fn matching(&self, held_keys: &[Key]) -> Option<&Hotkey> {
+        self.hotkeys.iter().find(|hotkey| {
+            !hotkey.keys.is_empty()
+                && hotkey.keys.len() == held_keys.len()
+                && hotkey.keys.iter().all(|key| held_keys.contains(key))
+        })
+    }
+
+    /// Whether the held keys are modifiers that could still become a hotkey. They are held back until
+    /// the chord either completes or breaks.
+    pub fn is_prefix(&self, held_keys: &[Key]) -> bool {
+        !held_keys.is_empty()
+            && held_keys.iter().all(|key| key.is_modifier())
+            && self.hotkeys.iter().any(|hotkey| {
+                hotkey.keys.len() > held_keys.len()
+                    && held_keys.iter().all(|key| hotkey.keys.contains(key))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, device_query::Keycode};
+
+    fn hotkeys() -> Hotkeys {
+        Hotkeys::new(vec![Hotkey {
+            keys: vec![
+                Key::KeyCode(Keycode::LControl),
+                Key::KeyCode(Keycode::LAlt),
+                Key::KeyCode(Keycode::Right),
+            ],
+            action: HotkeyAction::Next,
+        }])
+    }
+
+    #[test]
+    fn modifiers_of_a_hotkey_are_a_prefix() {
+        let hotkeys = hotkeys();
+        assert!(hotkeys.is_prefix(&[Key::KeyCode(Keycode::LControl)]));
+        assert!(hotkeys.is_prefix(&[Key::KeyCode(Keycode::LAlt), Key::KeyCode(Keycode::LControl)]));
+    }
+
+    #[test]
+    fn broken_chords_are_no_prefix() {
+        let hotkeys = hotkeys();
+        assert!(!hotkeys.is_prefix(&[]));
+        assert!(!hotkeys.is_prefix(&[Key::KeyCode(Keycode::LShift)]));
+        assert!(!hotkeys.is_prefix(&[Key::KeyCode(Keycode::LControl), Key::KeyCode(Keycode::C)]));
+        //non modifiers are never held back
+        assert!(!hotkeys.is_prefix(&[Key::KeyCode(Keycode::Right)]));
+    }
+
+    #[test]
+    fn complete_chords_match() {
+        let hotkeys = hotkeys();
+        let held = [
+            Key::KeyCode(Keycode::LAlt),
+            Key::KeyCode(Keycode::LControl),
+            Key::KeyCode(Keycode::Right),
+        ];
+        assert!(!hotkeys.is_prefix(&held));
+        assert_eq!(
+            hotkeys.matching(&held).map(|v| v.action.clone()),
+            Some(HotkeyAction::Next)
+        );
+        assert!(hotkeys.matching(&held[..2]).is_none());
+    }
+}
@@ -2,13 +2,24 @@ use std::time::Duration;
 
 use {
     crate::{
-        display::Client,
-        mouse_handler::Handler,
-        mouse_handler::Handler as MouseHandler,
-        protocol::{EventHandler, ProtocolError},
+        config::KeyTransport,
+        display::{Client, OsFamily},
+        hotkeys::{HotkeyAction, Hotkeys},
+        mouse_handler::{Handler, Handoff},
+        protocol::{Event, EventHandler, ProtocolError},
+        remap::Remapper,
+        scancode,
     },
     device_query::{CallbackGuard, DeviceEvents, DeviceState, Keycode},
-    std::{ops, str::FromStr, sync::Arc},
+    std::{
+        net::SocketAddrV4,
+        ops,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    },
     tokio::{runtime::Handle, sync::Mutex},
 };
 
@@ -150,13 +161,49 @@ impl TryFrom<String> for Direction {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum Key {
     KeyCode(Keycode),
+    Scancode(u16),
     MouseButton(usize),
 }
 
 impl Key {
+    pub fn with_transport(self, transport: KeyTransport) -> Key {
+        match (transport, &self) {
+            (KeyTransport::Scancode, Key::KeyCode(keycode)) => {
+                match scancode::keycode_to_usage_id(keycode) {
+                    Some(v) => Key::Scancode(v),
+                    None => self,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// Modifiers and mouse buttons, the keys whose state carries over when the cursor changes machine
+    pub fn is_modifier(&self) -> bool {
+        match self {
+            Key::KeyCode(code) => matches!(
+                code,
+                Keycode::LShift
+                    | Keycode::RShift
+                    | Keycode::LControl
+                    | Keycode::RControl
+                    | Keycode::LAlt
+                    | Keycode::RAlt
+                    | Keycode::LMeta
+                    | Keycode::RMeta
+                    | Keycode::Command
+                    | Keycode::LOption
+                    | Keycode::ROption
+            ),
+            Key::Scancode(code) => (0xE0..=0xE7).contains(code),
+            Key::MouseButton(_) => true,
+        }
+    }
+
     fn prepare_text(prefix: &'static str, text: &mut String) {
         for _ in 0..prefix.len() {
             text.remove(0);
@@ -168,11 +215,18 @@ impl From<&Key> for String {
     fn from(value: &Key) -> Self {
         match value {
             Key::KeyCode(code) => String::from("K_".to_string() + &code.to_string()),
+            Key::Scancode(code) => String::from("S_".to_string() + &code.to_string()),
             Key::MouseButton(index) => String::from("M_".to_string() + &index.to_string()),
         }
     }
 }
 
+impl From<Key> for String {
+    fn from(value: Key) -> Self {
+        String::from(&value)
+    }
+}
+
 impl From<Keycode> for Key {
     fn from(value: Keycode) -> Self {
         Key::KeyCode(value)
@@ -198,6 +252,16 @@ impl TryFrom<String> for Key {
                     format!("Unable to get key for: {}. Error: {}", cut_text, e),
                 )),
             };
+        } else if value.starts_with("S_") {
+            let mut cut_text = value.clone();
+            Key::prepare_text("S_", &mut cut_text);
+            return match cut_text.parse::<u16>() {
+                Ok(v) => Ok(Key::Scancode(v)),
+                Err(e) => Err(ProtocolError::ParserError(
+                    "Key Parser",
+                    format!("Unable to get scancode for: {}. Error: {}", cut_text, e),
+                )),
+            };
         } else if value.starts_with("M_") {
             let mut cut_text = value.clone();
             Key::prepare_text("M_", &mut cut_text);
@@ -255,108 +319,415 @@ impl KeyInput {
     }
 }
 
-pub struct HeldKeysManager {
-    mouse_handler: Arc<Mutex<MouseHandler>>,
-    event_handler: Arc<EventHandler>,
-    held_keys: Arc<Mutex<Vec<Key>>>,
+/// Text that is injected as a whole on the target, independent of its keyboard layout.
+/// Long text is split into `count` parts that share an `id`, udp does not keep them in order.
+#[derive(Debug, Clone)]
+pub struct TextInput {
+    pub id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub text: String,
 }
 
-impl HeldKeysManager {
-    pub fn new(mouse_handler: Arc<Mutex<MouseHandler>>, event_handler: Arc<EventHandler>) -> Self {
-        let instance = HeldKeysManager {
-            held_keys: Arc::new(Mutex::new(Vec::new())),
-            mouse_handler,
-            event_handler,
-        };
+static NEXT_TEXT_ID: AtomicU32 = AtomicU32::new(0);
 
-        instance.send_loop();
+impl TextInput {
+    const MAX_BYTES: usize = 1000;
 
-        instance
+    pub fn new(id: u32, index: u16, count: u16, text: String) -> TextInput {
+        TextInput {
+            id,
+            index,
+            count,
+            text,
+        }
     }
 
-    pub async fn key_input(&mut self, key_input: &KeyInput) {
-        match key_input.direction {
-            Direction::Up => {
-                HeldKeysManager::send_event(
-                    key_input.clone(),
-                    self.mouse_handler.clone(),
-                    self.event_handler.clone(),
-                )
-                .await;
-                self.held_keys
-                    .lock()
-                    .await
-                    .retain(|key| key != &key_input.key)
+    /// Splits the text so that every part fits into a single udp package
+    pub fn split(text: &str) -> Vec<TextInput> {
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        for character in text.chars() {
+            if current.len() + character.len_utf8() > TextInput::MAX_BYTES {
+                parts.push(current);
+                current = String::new();
             }
-            Direction::Down => self.held_keys.lock().await.push(key_input.key.clone()),
+            current.push(character);
         }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+        let id = NEXT_TEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let count = parts.len() as u16;
+        parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| TextInput::new(id, index as u16, count, text))
+            .collect()
     }
+}
 
-    fn send_loop(&self) {
-        let held_keys_clone = self.held_keys.clone();
-        let mouse_handler_clone = self.mouse_handler.clone();
-        let event_handler_clone = self.event_handler.clone();
-        tokio::spawn(async move {
-            loop {
+impl TryFrom<String> for TextInput {
+    type Error = ProtocolError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut split = value.splitn(4, "|");
+        let mut number = |name: &str| -> Result<u32, Self::Error> {
+            match split.next().map(|v| v.parse::<u32>()) {
+                Some(Ok(v)) => Ok(v),
+                _ => Err(ProtocolError::ParserError(
+                    "Text input",
+                    format!("Unable to parse the {} of the text", name),
+                )),
+            }
+        };
+        let id = number("id")?;
+        let index = number("index")? as u16;
+        let count = number("count")? as u16;
+        let text = match split.next() {
+            Some(v) => v.to_string(),
+            None => {
+                return Err(ProtocolError::ParserError(
+                    "Text input",
+                    String::from("Text input has no text"),
+                ))
+            }
+        };
+        if index >= count {
+            return Err(ProtocolError::ParserError(
+                "Text input",
+                format!("Part {} of {} does not exist", index, count),
+            ));
+        }
+        Ok(TextInput::new(id, index, count, text))
+    }
+}
+
+impl From<&TextInput> for String {
+    fn from(value: &TextInput) -> Self {
+        format!(
+            "{}|{}|{}|{}",
+            value.id, value.index, value.count, value.text
+        )
+    }
+}
+
+/// Collects the parts of a split text until all of them arrived
+pub struct TextAssembler {
+    id: Option<u32>,
+    parts: Vec<Option<String>>,
+}
+
+impl TextAssembler {
+    pub fn new() -> TextAssembler {
+        TextAssembler {
+            id: None,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Returns the whole text once its last part arrived. A part of a newer text drops the
+    /// unfinished one, its missing parts were lost.
+    pub fn add(&mut self, input: TextInput) -> Option<String> {
+        if self.id != Some(input.id) || self.parts.len() != input.count as usize {
+            self.id = Some(input.id);
+            self.parts = vec![None; input.count as usize];
+        }
+        self.parts[input.index as usize] = Some(input.text);
+        if self.parts.iter().any(|part| part.is_none()) {
+            return None;
+        }
+        self.id = None;
+        Some(self.parts.drain(..).flatten().collect())
+    }
+}
+
+/// The state of one keyboard shared across all machines
+pub struct KeyState {
+    pub keys: Vec<Key>,
+}
+
+impl KeyState {
+    pub fn new(keys: Vec<Key>) -> KeyState {
+        KeyState { keys }
+    }
+}
+
+impl TryFrom<String> for KeyState {
+    type Error = ProtocolError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut keys = Vec::new();
+        for key in value.split(",").filter(|v| !v.is_empty()) {
+            keys.push(Key::try_from(String::from(key))?);
+        }
+        Ok(KeyState::new(keys))
+    }
+}
+
+impl From<&KeyState> for String {
+    fn from(value: &KeyState) -> Self {
+        value
+            .keys
+            .iter()
+            .map(String::from)
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+struct TargetState {
+    target: Option<(SocketAddrV4, Option<OsFamily>)>,
+    held_keys: Vec<Key>,
+    /// Keys that were held when the cursor entered the current target. They are not forwarded until
+    /// they are released, so the target never sees a key it did not get a press for.
+    suppressed_keys: Vec<Key>,
+    sent_keys: Vec<Key>,
+}
+
+/// Sends the held keys to the client under the cursor after applying the remapping rules
+pub struct KeySender {
+    event_handler: Arc<EventHandler>,
+    remapper: Remapper,
+    hotkeys: Hotkeys,
+    key_transport: KeyTransport,
+    state: Mutex<TargetState>,
+}
+
+impl KeySender {
+    pub fn new(
+        event_handler: Arc<EventHandler>,
+        remapper: Remapper,
+        hotkeys: Hotkeys,
+        key_transport: KeyTransport,
+    ) -> Self {
+        KeySender {
+            event_handler,
+            remapper,
+            hotkeys,
+            key_transport,
+            state: Mutex::new(TargetState {
+                target: None,
+                held_keys: Vec::new(),
+                suppressed_keys: Vec::new(),
+                sent_keys: Vec::new(),
+            }),
+        }
+    }
+
+    /// Returns the action of the hotkey this input completed
+    pub async fn key_input(&self, key_input: &KeyInput) -> Option<HotkeyAction> {
+        let mut state = self.state.lock().await;
+        let mut action = None;
+        match key_input.direction {
+            Direction::Up => {
+                if self.hotkeys.is_prefix(&state.held_keys)
+                    && !state.suppressed_keys.contains(&key_input.key)
                 {
-                    let mut send_keys: Vec<Key> = Vec::new();
-                    {
-                        let held_keys_lck = held_keys_clone.lock().await;
-                        for key in held_keys_lck.iter() {
-                            send_keys.push(key.clone());
-                        }
-                    }
-                    for key in send_keys {
-                        HeldKeysManager::send_event(
-                            KeyInput::new(key, Direction::Down),
-                            mouse_handler_clone.clone(),
-                            event_handler_clone.clone(),
-                        )
-                        .await;
+                    // the chord broke without the target ever seeing the modifier, so it gets a tap
+                    self.tap(&state, &key_input.key).await;
+                }
+                state.held_keys.retain(|key| key != &key_input.key);
+                state.suppressed_keys.retain(|key| key != &key_input.key);
+            }
+            Direction::Down => {
+                if !state.held_keys.contains(&key_input.key) {
+                    state.held_keys.push(key_input.key.clone());
+                    if let Some(hotkey) = self.hotkeys.matching(&state.held_keys) {
+                        // keep the hotkey away from the target. Modifiers it already got are released by the sync.
+                        state.suppressed_keys.extend(hotkey.keys.iter().cloned());
+                        action = Some(hotkey.action.clone());
                     }
                 }
-                tokio::time::sleep(Duration::from_millis(25)).await;
             }
-        });
+        }
+        self.sync_state(&mut state).await;
+        action
     }
 
-    async fn send_event(
-        key_input: KeyInput,
-        mouse_handler: Arc<Mutex<MouseHandler>>,
-        event_handler: Arc<EventHandler>,
-    ) {
-        let mouse_client_res;
-        {
-            let mut lock = mouse_handler.lock().await;
-            mouse_client_res = lock.get_local_mouse_position().await;
+    async fn tap(&self, state: &TargetState, key: &Key) {
+        let (client_addr, os) = match state.target {
+            Some(v) => v,
+            None => return,
+        };
+        for key in self.snapshot_keys(std::slice::from_ref(key), &client_addr, os) {
+            self.send_event(
+                client_addr,
+                Box::new(KeyInput::new(key.clone(), Direction::Down)),
+            )
+            .await;
+            self.send_event(client_addr, Box::new(KeyInput::new(key, Direction::Up)))
+                .await;
+        }
+    }
+
+    pub async fn held_keys(&self) -> Vec<Key> {
+        self.state.lock().await.held_keys.clone()
+    }
+
+    pub async fn sync(&self) {
+        let mut state = self.state.lock().await;
+        self.sync_state(&mut state).await;
+    }
+
+    /// Presses every remapped held key on the target and releases the ones no longer held
+    async fn sync_state(&self, state: &mut TargetState) {
+        let (client_addr, os) = match state.target {
+            Some(v) => v,
+            None => return,
+        };
+        let keys = self.target_keys(state, &client_addr, os);
+
+        for key in state.sent_keys.iter().filter(|key| !keys.contains(key)) {
+            self.send_event(
+                client_addr,
+                Box::new(KeyInput::new(key.clone(), Direction::Up)),
+            )
+            .await;
         }
-        let mouse_client_position = match mouse_client_res {
+        for key in keys.iter() {
+            self.send_event(
+                client_addr,
+                Box::new(KeyInput::new(key.clone(), Direction::Down)),
+            )
+            .await;
+        }
+        state.sent_keys = keys;
+    }
+
+    fn target_keys(
+        &self,
+        state: &TargetState,
+        client_addr: &SocketAddrV4,
+        os: Option<OsFamily>,
+    ) -> Vec<Key> {
+        //modifiers that may still become a hotkey wait until the chord resolves
+        if self.hotkeys.is_prefix(&state.held_keys) {
+            return Vec::new();
+        }
+        let pressed_keys: Vec<Key> = state
+            .held_keys
+            .iter()
+            .filter(|key| !state.suppressed_keys.contains(key))
+            .cloned()
+            .collect();
+        self.snapshot_keys(&pressed_keys, client_addr, os)
+    }
+
+    /// Remaps the keys for the target and converts them to the configured transport
+    fn snapshot_keys(
+        &self,
+        keys: &[Key],
+        client_addr: &SocketAddrV4,
+        os: Option<OsFamily>,
+    ) -> Vec<Key> {
+        self.remapper
+            .apply(client_addr, os, keys)
+            .into_iter()
+            .map(|key| key.with_transport(self.key_transport))
+            .collect()
+    }
+
+    async fn send_event(&self, client_addr: SocketAddrV4, event: Box<dyn Event>) {
+        match self
+            .event_handler
+            .specific_communication(client_addr, event)
+            .await
+        {
             Err(e) => {
-                return println!(
-                    "Unable to read mouse position to transmit keys to the correct client: {}",
-                    e
+                println!("Error sending key: {}", e)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Handoff for KeySender {
+    async fn handoff(&self, client: &Client, os: Option<OsFamily>) {
+        let mut state = self.state.lock().await;
+        if let Some((old_addr, _)) = state.target {
+            for key in state.sent_keys.iter() {
+                self.send_event(
+                    old_addr,
+                    Box::new(KeyInput::new(key.clone(), Direction::Up)),
                 )
+                .await;
             }
-            Ok(v) => v,
+        }
+        state.sent_keys.clear();
+        state.target = match client {
+            Client::IsNetworked(v) => Some((*v, os)),
+            Client::IsSelf => None,
         };
 
-        let client_addr = match mouse_client_position.client {
-            Client::IsNetworked(v) => v,
-            Client::IsSelf => {
-                return;
-            }
+        //the held modifiers and buttons carry over, everything else stays with the old target
+        let carried: Vec<Key> = state
+            .held_keys
+            .iter()
+            .filter(|key| key.is_modifier() && !state.suppressed_keys.contains(key))
+            .cloned()
+            .collect();
+        if let Some((client_addr, os)) = state.target {
+            let keys = match self.hotkeys.is_prefix(&state.held_keys) {
+                true => Vec::new(),
+                false => self.snapshot_keys(&carried, &client_addr, os),
+            };
+            self.send_event(client_addr, Box::new(KeyState::new(keys.clone())))
+                .await;
+            state.sent_keys = keys;
+        }
+        state.suppressed_keys = state
+            .held_keys
+            .iter()
+            .filter(|key| !carried.contains(key))
+            .cloned()
+            .collect();
+    }
+}
+
+pub struct HeldKeysManager {
+    key_sender: Arc<KeySender>,
+    mouse_handler: Arc<Mutex<Handler>>,
+}
+
+impl HeldKeysManager {
+    pub fn new(key_sender: Arc<KeySender>, mouse_handler: Arc<Mutex<Handler>>) -> Self {
+        let instance = HeldKeysManager {
+            key_sender,
+            mouse_handler,
         };
 
-        match event_handler
-            .specific_communication(client_addr, Box::new(key_input))
-            .await
-        {
+        instance.send_loop();
+
+        instance
+    }
+
+    pub async fn key_input(&mut self, key_input: &KeyInput) {
+        let action = self.key_sender.key_input(key_input).await;
+        // the key sender is unlocked again, the mouse handler locks it during a handoff
+        let held_keys = self.key_sender.held_keys().await;
+        let mut mouse_handler = self.mouse_handler.lock().await;
+        mouse_handler.set_held_keys(held_keys);
+        let action = match action {
+            Some(v) => v,
+            None => return,
+        };
+        match mouse_handler.run_hotkey(&action).await {
             Err(e) => {
-                println!("Error sending key: {}", e)
+                println!("Unable to run hotkey {:?}: {}", action, e)
             }
             _ => {}
         }
     }
+
+    fn send_loop(&self) {
+        let key_sender_clone = self.key_sender.clone();
+        tokio::spawn(async move {
+            loop {
+                key_sender_clone.sync().await;
+                tokio::time::sleep(Duration::from_millis(25)).await;
+            }
+        });
+    }
 }
 
 pub struct KeyInputReceiver {
@@ -365,12 +736,12 @@ pub struct KeyInputReceiver {
 }
 
 impl KeyInputReceiver {
-    pub fn new(event_handler: Arc<EventHandler>, mouse_handler: Arc<Mutex<MouseHandler>>) -> Self {
+    pub fn new(key_sender: Arc<KeySender>, mouse_handler: Arc<Mutex<Handler>>) -> Self {
         KeyInputReceiver {
             keys: DeviceState::new(),
             held_keys_manager: Arc::new(Mutex::new(HeldKeysManager::new(
-                mouse_handler.clone(),
-                event_handler.clone(),
+                key_sender,
+                mouse_handler,
             ))),
         }
     }
@@ -452,3 +823,57 @@ impl KeyInputReceiver {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_input_survives_serialization() {
+        let input = TextInput::new(7, 1, 3, String::from("a|b ü"));
+        let parsed = TextInput::try_from(String::from(&input)).unwrap();
+        assert_eq!(
+            (parsed.id, parsed.index, parsed.count, parsed.text),
+            (7, 1, 3, String::from("a|b ü"))
+        );
+        assert!(TextInput::try_from(String::from("7|3|3|x")).is_err());
+        assert!(TextInput::try_from(String::from("7|x")).is_err());
+    }
+
+    #[test]
+    fn split_text_is_reassembled_in_order() {
+        let text: String = (0..2500)
+            .map(|i| if i % 2 == 0 { 'ä' } else { 'b' })
+            .collect();
+        let mut parts = TextInput::split(&text);
+        assert!(parts.len() > 1);
+        assert!(parts
+            .iter()
+            .all(|part| part.text.len() <= TextInput::MAX_BYTES));
+        parts.reverse();
+
+        let mut assembler = TextAssembler::new();
+        let last = parts.pop().unwrap();
+        for part in parts {
+            assert_eq!(assembler.add(part), None);
+        }
+        assert_eq!(assembler.add(last), Some(text));
+    }
+
+    #[test]
+    fn newer_text_drops_an_unfinished_one() {
+        let mut assembler = TextAssembler::new();
+        assert_eq!(
+            assembler.add(TextInput::new(1, 0, 2, String::from("lost"))),
+            None
+        );
+        assert_eq!(
+            assembler.add(TextInput::new(2, 0, 1, String::from("new"))),
+            Some(String::from("new"))
+        );
+        assert_eq!(
+            assembler.add(TextInput::new(1, 1, 2, String::from("late"))),
+            None
+        );
+    }
+}
@@ -1,7 +1,10 @@
 use {
-    crate::input::{Direction, Key as InputKey, KeyInput},
+    crate::{
+        input::{Direction, Key as InputKey, KeyInput, KeyState, TextAssembler, TextInput},
+        scancode,
+    },
     device_query::keymap::Keycode,
-    enigo::{keycodes::Key, Enigo, KeyboardControllable, MouseButton, MouseControllable},
+    enigo::{Button, Direction as EnigoDirection, Enigo, Key, Keyboard, Mouse},
     std::{fmt, time::Duration, time::Instant},
 };
 
@@ -10,6 +13,7 @@ type Result<T> = std::result::Result<T, KeyError>;
 #[derive(Debug)]
 pub enum KeyError {
     TransformationError,
+    InputError(enigo::InputError),
 }
 
 impl std::fmt::Display for KeyError {
@@ -21,131 +25,238 @@ impl std::fmt::Display for KeyError {
                     "Was unable to transform a device_query key into an enigo key."
                 )
             }
+            KeyError::InputError(e) => write!(f, "Unable to simulate input: {}", e),
         }
     }
 }
 
 impl std::error::Error for KeyError {}
 
+impl From<enigo::InputError> for KeyError {
+    fn from(value: enigo::InputError) -> Self {
+        KeyError::InputError(value)
+    }
+}
+
 pub struct Handler {
     enigo: Enigo,
     keys_manager: KeysManager,
+    text_assembler: TextAssembler,
 }
 
 impl Handler {
     pub fn new() -> Handler {
         Handler {
-            enigo: Enigo::new(&enigo::Settings::default()),
+            enigo: Enigo::new(&enigo::Settings::default()).unwrap(),
             keys_manager: KeysManager::new(),
+            text_assembler: TextAssembler::new(),
         }
     }
 
     pub fn timed_update(&mut self) {
         let release = self.keys_manager.time_update();
         for rel in release {
-            match rel {
-                EnigoKey::KeyboardButton(key) => {
-                    self.enigo.key_up(key);
-                }
-                EnigoKey::MouseButton(mb) => {
-                    println!("------mouse_up------");
-                    self.enigo.mouse_up(mb);
-                }
-            }
+            self.release(rel);
         }
     }
 
+    fn release(&mut self, key: EnigoKey) {
+        if let Err(e) = self.press(&key, EnigoDirection::Release) {
+            println!("Unable to release {:?}: {}", key, e)
+        }
+    }
+
+    fn press(&mut self, key: &EnigoKey, direction: EnigoDirection) -> Result<()> {
+        match key {
+            EnigoKey::KeyboardButton(key) => self.enigo.key(*key, direction)?,
+            EnigoKey::Raw(keycode) => self.enigo.raw(*keycode, direction)?,
+            EnigoKey::MouseButton(mb) => self.enigo.button(*mb, direction)?,
+            EnigoKey::Composed(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Makes the pressed keys match the snapshot of the machine that took over the cursor
+    pub fn received_key_state(&mut self, key_state: KeyState) -> Result<()> {
+        let keep: Vec<EnigoKey> = key_state
+            .keys
+            .iter()
+            .filter_map(|key| input_key_to_enigo_key(key).ok())
+            .collect();
+        for key in self.keys_manager.release_except(&keep) {
+            self.release(key);
+        }
+        for key in key_state.keys {
+            self.received_key(KeyInput::new(key, Direction::Down))?;
+        }
+        Ok(())
+    }
+
     pub fn received_key(&mut self, key_input: KeyInput) -> Result<()> {
-        match key_input.key {
-            InputKey::KeyCode(keycode) => {
-                let key = device_query_keycode_to_enigo_key(&keycode)?;
-                match (
-                    &key_input.direction,
-                    self.keys_manager
-                        .received_key_update(&EnigoKey::KeyboardButton(key), &key_input.direction),
-                ) {
-                    (Direction::Down, true) => {
-                        self.enigo.key_down(key);
-                    }
-                    (Direction::Up, true) => {
-                        self.enigo.key_up(key);
-                    }
-                    _ => {}
-                };
-            }
-            InputKey::MouseButton(button) => {
-                let mouse_button = mouse_button_to_enigo_mouse_button(button)?;
-                match (
-                    &key_input.direction,
-                    self.keys_manager.received_key_update(
-                        &EnigoKey::MouseButton(mouse_button),
-                        &key_input.direction,
-                    ),
-                ) {
-                    (Direction::Down, true) => {
-                        self.enigo
-                            .mouse_down(mouse_button_to_enigo_mouse_button(button)?);
-                    }
-                    (Direction::Up, true) => self
-                        .enigo
-                        .mouse_up(mouse_button_to_enigo_mouse_button(button)?),
-                    _ => {}
+        let key = match input_key_to_enigo_key(&key_input.key) {
+            Ok(v) => v,
+            Err(KeyError::TransformationError) => match key_input.key {
+                InputKey::KeyCode(keycode) => {
+                    return self.received_composed_key(&keycode, &key_input.direction);
                 }
-            }
+                _ => return Err(KeyError::TransformationError),
+            },
+            Err(e) => return Err(e),
+        };
+        match (
+            &key_input.direction,
+            self.keys_manager
+                .received_key_update(&key, &key_input.direction),
+        ) {
+            (Direction::Down, true) => self.press(&key, EnigoDirection::Press)?,
+            (Direction::Up, true) => self.press(&key, EnigoDirection::Release)?,
+            _ => {}
+        };
+        Ok(())
+    }
+
+    /// Types the text as unicode once all of its parts arrived
+    pub fn received_text(&mut self, text_input: TextInput) {
+        let text = match self.text_assembler.add(text_input) {
+            Some(v) => v,
+            None => return,
+        };
+        if let Err(e) = self.enigo.text(&text) {
+            println!("Unable to type text: {}", e)
+        }
+    }
+
+    /// Fallback for keys enigo can not press: types the character the key produces instead.
+    /// `received_text` only covers text sent on purpose, ordinary typing still arrives as keys.
+    fn received_composed_key(&mut self, keycode: &Keycode, direction: &Direction) -> Result<()> {
+        let shift = self
+            .keys_manager
+            .is_pressed(&EnigoKey::KeyboardButton(Key::LShift))
+            || self
+                .keys_manager
+                .is_pressed(&EnigoKey::KeyboardButton(Key::RShift));
+        let character = match keycode_to_character(keycode, shift) {
+            Some(v) => v,
+            None => return Err(KeyError::TransformationError),
+        };
+        let composed = EnigoKey::Composed(character);
+        match (
+            direction,
+            self.keys_manager.received_key_update(&composed, direction),
+        ) {
+            (Direction::Down, true) => self.enigo.text(&character.to_string())?,
+            _ => {}
         }
         Ok(())
     }
 }
 
-fn mouse_button_to_enigo_mouse_button(mouse_button: usize) -> Result<MouseButton> {
+/// The character a key produces on a US layout. Used when a key can not be pressed directly.
+fn keycode_to_character(key: &Keycode, shift: bool) -> Option<char> {
+    let (normal, shifted) = match key {
+        Keycode::Grave => ('`', '~'),
+        Keycode::Minus => ('-', '_'),
+        Keycode::Equal => ('=', '+'),
+        Keycode::LeftBracket => ('[', '{'),
+        Keycode::RightBracket => (']', '}'),
+        Keycode::BackSlash => ('\\', '|'),
+        Keycode::Semicolon => (';', ':'),
+        Keycode::Apostrophe => ('\'', '"'),
+        Keycode::Comma => (',', '<'),
+        Keycode::Dot => ('.', '>'),
+        Keycode::Slash => ('/', '?'),
+        Keycode::Numpad0 => ('0', '0'),
+        Keycode::Numpad1 => ('1', '1'),
+        Keycode::Numpad2 => ('2', '2'),
+        Keycode::Numpad3 => ('3', '3'),
+        Keycode::Numpad4 => ('4', '4'),
+        Keycode::Numpad5 => ('5', '5'),
+        Keycode::Numpad6 => ('6', '6'),
+        Keycode::Numpad7 => ('7', '7'),
+        Keycode::Numpad8 => ('8', '8'),
+        Keycode::Numpad9 => ('9', '9'),
+        Keycode::NumpadSubtract => ('-', '-'),
+        Keycode::NumpadAdd => ('+', '+'),
+        Keycode::NumpadDivide => ('/', '/'),
+        Keycode::NumpadMultiply => ('*', '*'),
+        Keycode::NumpadEquals => ('=', '='),
+        Keycode::NumpadDecimal => ('.', '.'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { normal })
+}
+
+fn input_key_to_enigo_key(key: &InputKey) -> Result<EnigoKey> {
+    match key {
+        InputKey::KeyCode(keycode) => Ok(EnigoKey::KeyboardButton(
+            device_query_keycode_to_enigo_key(keycode)?,
+        )),
+        InputKey::Scancode(usage_id) => match scancode::usage_id_to_raw(*usage_id) {
+            Some(v) => Ok(EnigoKey::Raw(v)),
+            None => Err(KeyError::TransformationError),
+        },
+        InputKey::MouseButton(button) => Ok(EnigoKey::MouseButton(
+            mouse_button_to_enigo_mouse_button(*button)?,
+        )),
+    }
+}
+
+fn mouse_button_to_enigo_mouse_button(mouse_button: usize) -> Result<Button> {
     match mouse_button {
-        1 => Ok(MouseButton::Left),
-        2 => Ok(MouseButton::Right),
-        3 => Ok(MouseButton::Middle),
-        4 => Ok(MouseButton::Back),
-        5 => Ok(MouseButton::Forward),
+        1 => Ok(Button::Left),
+        2 => Ok(Button::Right),
+        3 => Ok(Button::Middle),
+        #[cfg(not(target_os = "macos"))]
+        4 => Ok(Button::Back),
+        #[cfg(not(target_os = "macos"))]
+        5 => Ok(Button::Forward),
         _ => Err(KeyError::TransformationError),
     }
 }
 
+/// Translates every key device_query can report. The match is intentionally exhaustive so a new
+/// device_query keycode fails to compile instead of silently not reaching the target.
+///
+/// Unsupported: device_query does not report the Menu key, F21 and above or any media keys,
+/// so they never reach this function.
 fn device_query_keycode_to_enigo_key(key: &Keycode) -> Result<Key> {
     match key {
-        Keycode::A => Ok(Key::Layout('a')),
-        Keycode::B => Ok(Key::Layout('b')),
-        Keycode::C => Ok(Key::Layout('c')),
-        Keycode::D => Ok(Key::Layout('d')),
-        Keycode::E => Ok(Key::Layout('e')),
-        Keycode::F => Ok(Key::Layout('f')),
-        Keycode::G => Ok(Key::Layout('g')),
-        Keycode::H => Ok(Key::Layout('h')),
-        Keycode::I => Ok(Key::Layout('i')),
-        Keycode::J => Ok(Key::Layout('j')),
-        Keycode::K => Ok(Key::Layout('k')),
-        Keycode::L => Ok(Key::Layout('l')),
-        Keycode::M => Ok(Key::Layout('m')),
-        Keycode::N => Ok(Key::Layout('n')),
-        Keycode::O => Ok(Key::Layout('o')),
-        Keycode::P => Ok(Key::Layout('p')),
-        Keycode::Q => Ok(Key::Layout('q')),
-        Keycode::R => Ok(Key::Layout('r')),
-        Keycode::S => Ok(Key::Layout('s')),
-        Keycode::T => Ok(Key::Layout('t')),
-        Keycode::U => Ok(Key::Layout('u')),
-        Keycode::V => Ok(Key::Layout('v')),
-        Keycode::W => Ok(Key::Layout('w')),
-        Keycode::X => Ok(Key::Layout('x')),
-        Keycode::Y => Ok(Key::Layout('y')),
-        Keycode::Z => Ok(Key::Layout('z')),
-        Keycode::Key0 => Ok(Key::Layout('0')),
-        Keycode::Key1 => Ok(Key::Layout('1')),
-        Keycode::Key2 => Ok(Key::Layout('2')),
-        Keycode::Key3 => Ok(Key::Layout('3')),
-        Keycode::Key4 => Ok(Key::Layout('4')),
-        Keycode::Key5 => Ok(Key::Layout('5')),
-        Keycode::Key6 => Ok(Key::Layout('6')),
-        Keycode::Key7 => Ok(Key::Layout('7')),
-        Keycode::Key8 => Ok(Key::Layout('8')),
-        Keycode::Key9 => Ok(Key::Layout('9')),
+        Keycode::A => Ok(Key::Unicode('a')),
+        Keycode::B => Ok(Key::Unicode('b')),
+        Keycode::C => Ok(Key::Unicode('c')),
+        Keycode::D => Ok(Key::Unicode('d')),
+        Keycode::E => Ok(Key::Unicode('e')),
+        Keycode::F => Ok(Key::Unicode('f')),
+        Keycode::G => Ok(Key::Unicode('g')),
+        Keycode::H => Ok(Key::Unicode('h')),
+        Keycode::I => Ok(Key::Unicode('i')),
+        Keycode::J => Ok(Key::Unicode('j')),
+        Keycode::K => Ok(Key::Unicode('k')),
+        Keycode::L => Ok(Key::Unicode('l')),
+        Keycode::M => Ok(Key::Unicode('m')),
+        Keycode::N => Ok(Key::Unicode('n')),
+        Keycode::O => Ok(Key::Unicode('o')),
+        Keycode::P => Ok(Key::Unicode('p')),
+        Keycode::Q => Ok(Key::Unicode('q')),
+        Keycode::R => Ok(Key::Unicode('r')),
+        Keycode::S => Ok(Key::Unicode('s')),
+        Keycode::T => Ok(Key::Unicode('t')),
+        Keycode::U => Ok(Key::Unicode('u')),
+        Keycode::V => Ok(Key::Unicode('v')),
+        Keycode::W => Ok(Key::Unicode('w')),
+        Keycode::X => Ok(Key::Unicode('x')),
+        Keycode::Y => Ok(Key::Unicode('y')),
+        Keycode::Z => Ok(Key::Unicode('z')),
+        Keycode::Key0 => Ok(Key::Unicode('0')),
+        Keycode::Key1 => Ok(Key::Unicode('1')),
+        Keycode::Key2 => Ok(Key::Unicode('2')),
+        Keycode::Key3 => Ok(Key::Unicode('3')),
+        Keycode::Key4 => Ok(Key::Unicode('4')),
+        Keycode::Key5 => Ok(Key::Unicode('5')),
+        Keycode::Key6 => Ok(Key::Unicode('6')),
+        Keycode::Key7 => Ok(Key::Unicode('7')),
+        Keycode::Key8 => Ok(Key::Unicode('8')),
+        Keycode::Key9 => Ok(Key::Unicode('9')),
         Keycode::F1 => Ok(Key::F1),
         Keycode::F2 => Ok(Key::F2),
         Keycode::F3 => Ok(Key::F3),
@@ -158,29 +269,143 @@ fn device_query_keycode_to_enigo_key(key: &Keycode) -> Result<Key> {
         Keycode::F10 => Ok(Key::F10),
         Keycode::F11 => Ok(Key::F11),
         Keycode::F12 => Ok(Key::F12),
+        Keycode::F13 => Ok(Key::F13),
+        Keycode::F14 => Ok(Key::F14),
+        Keycode::F15 => Ok(Key::F15),
+        Keycode::F16 => Ok(Key::F16),
+        Keycode::F17 => Ok(Key::F17),
+        Keycode::F18 => Ok(Key::F18),
+        Keycode::F19 => Ok(Key::F19),
+        Keycode::F20 => Ok(Key::F20),
+        Keycode::Escape => Ok(Key::Escape),
+        Keycode::Space => Ok(Key::Space),
         Keycode::LControl => Ok(Key::LControl),
         Keycode::RControl => Ok(Key::RControl),
-        Keycode::Space => Ok(Key::Space),
-        Keycode::Backspace => Ok(Key::Backspace),
-        Keycode::Meta => Ok(Key::Meta),
-        Keycode::Escape => Ok(Key::Escape),
+        Keycode::LShift => Ok(Key::LShift),
+        Keycode::RShift => Ok(Key::RShift),
+        Keycode::LAlt => Ok(Key::Alt),
+        Keycode::RAlt => Ok(right_alt()),
+        Keycode::LMeta => Ok(Key::Meta),
+        Keycode::RMeta => Ok(right_meta()),
+        Keycode::Command => Ok(Key::Meta),
+        Keycode::LOption => Ok(Key::Alt),
+        Keycode::ROption => Ok(right_alt()),
         Keycode::Enter => Ok(Key::Return),
         Keycode::Up => Ok(Key::UpArrow),
         Keycode::Down => Ok(Key::DownArrow),
         Keycode::Left => Ok(Key::LeftArrow),
         Keycode::Right => Ok(Key::RightArrow),
-        Keycode::LAlt => Ok(Key::Alt),
-        Keycode::LShift => Ok(Key::LShift),
-        Keycode::RShift => Ok(Key::RShift),
+        Keycode::Backspace => Ok(Key::Backspace),
+        Keycode::CapsLock => Ok(Key::CapsLock),
         Keycode::Tab => Ok(Key::Tab),
-        _ => Err(KeyError::TransformationError),
+        Keycode::Home => Ok(Key::Home),
+        Keycode::End => Ok(Key::End),
+        Keycode::PageUp => Ok(Key::PageUp),
+        Keycode::PageDown => Ok(Key::PageDown),
+        Keycode::Insert => Ok(insert()),
+        Keycode::Delete => Ok(Key::Delete),
+        Keycode::Numpad0 => Ok(numpad_digit(0)),
+        Keycode::Numpad1 => Ok(numpad_digit(1)),
+        Keycode::Numpad2 => Ok(numpad_digit(2)),
+        Keycode::Numpad3 => Ok(numpad_digit(3)),
+        Keycode::Numpad4 => Ok(numpad_digit(4)),
+        Keycode::Numpad5 => Ok(numpad_digit(5)),
+        Keycode::Numpad6 => Ok(numpad_digit(6)),
+        Keycode::Numpad7 => Ok(numpad_digit(7)),
+        Keycode::Numpad8 => Ok(numpad_digit(8)),
+        Keycode::Numpad9 => Ok(numpad_digit(9)),
+        Keycode::NumpadSubtract => Ok(numpad_operator('-')),
+        Keycode::NumpadAdd => Ok(numpad_operator('+')),
+        Keycode::NumpadDivide => Ok(numpad_operator('/')),
+        Keycode::NumpadMultiply => Ok(numpad_operator('*')),
+        Keycode::NumpadDecimal => Ok(numpad_operator('.')),
+        Keycode::NumpadEquals => Ok(Key::Unicode('=')),
+        Keycode::NumpadEnter => Ok(Key::Return),
+        Keycode::Grave => Ok(Key::Unicode('`')),
+        Keycode::Minus => Ok(Key::Unicode('-')),
+        Keycode::Equal => Ok(Key::Unicode('=')),
+        Keycode::LeftBracket => Ok(Key::Unicode('[')),
+        Keycode::RightBracket => Ok(Key::Unicode(']')),
+        Keycode::BackSlash => Ok(Key::Unicode('\\')),
+        Keycode::Semicolon => Ok(Key::Unicode(';')),
+        Keycode::Apostrophe => Ok(Key::Unicode('\'')),
+        Keycode::Comma => Ok(Key::Unicode(',')),
+        Keycode::Dot => Ok(Key::Unicode('.')),
+        Keycode::Slash => Ok(Key::Unicode('/')),
     }
 }
 
+/// AltGr on Windows and Linux, the right option key on macOS
+fn right_alt() -> Key {
+    #[cfg(target_os = "windows")]
+    return Key::RMenu;
+    #[cfg(target_os = "macos")]
+    return Key::ROption;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return Key::Other(XK_ISO_LEVEL3_SHIFT);
+}
+
+/// The right windows key on Windows and super key on Linux, the right command key on macOS
+fn right_meta() -> Key {
+    #[cfg(target_os = "windows")]
+    return Key::RWin;
+    #[cfg(target_os = "macos")]
+    return Key::RCommand;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    return Key::Other(XK_SUPER_R);
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const XK_ISO_LEVEL3_SHIFT: u32 = 0xfe03;
+#[cfg(all(unix, not(target_os = "macos")))]
+const XK_SUPER_R: u32 = 0xffec;
+
+/// macOS keyboards have no insert key, help sits in its place
+fn insert() -> Key {
+    #[cfg(target_os = "macos")]
+    return Key::Help;
+    #[cfg(not(target_os = "macos"))]
+    return Key::Insert;
+}
+
+fn numpad_digit(digit: u8) -> Key {
+    #[cfg(target_os = "windows")]
+    return match digit {
+        0 => Key::Numpad0,
+        1 => Key::Numpad1,
+        2 => Key::Numpad2,
+        3 => Key::Numpad3,
+        4 => Key::Numpad4,
+        5 => Key::Numpad5,
+        6 => Key::Numpad6,
+        7 => Key::Numpad7,
+        8 => Key::Numpad8,
+        _ => Key::Numpad9,
+    };
+    #[cfg(not(target_os = "windows"))]
+    return Key::Unicode((b'0' + digit) as char);
+}
+
+fn numpad_operator(operator: char) -> Key {
+    #[cfg(target_os = "windows")]
+    return match operator {
+        '-' => Key::Subtract,
+        '+' => Key::Add,
+        '/' => Key::Divide,
+        '*' => Key::Multiply,
+        _ => Key::Decimal,
+    };
+    #[cfg(not(target_os = "windows"))]
+    return Key::Unicode(operator);
+}
+
 #[derive(Clone, Debug)]
 enum EnigoKey {
     KeyboardButton(Key),
-    MouseButton(MouseButton),
+    /// A platform keycode, see `scancode::usage_id_to_raw`
+    Raw(u16),
+    MouseButton(Button),
+    Composed(char),
 }
 
 impl PartialEq for EnigoKey {
@@ -188,6 +413,8 @@ impl PartialEq for EnigoKey {
         return match (self, other) {
             (EnigoKey::MouseButton(mb), EnigoKey::MouseButton(omb)) => mb == omb,
             (EnigoKey::KeyboardButton(kb), EnigoKey::KeyboardButton(okb)) => kb == okb,
+            (EnigoKey::Raw(r), EnigoKey::Raw(or)) => r == or,
+            (EnigoKey::Composed(c), EnigoKey::Composed(oc)) => c == oc,
             _ => false,
         };
     }
@@ -248,6 +475,25 @@ impl KeysManager {
         };
     }
 
+    pub fn is_pressed(&self, key: &EnigoKey) -> bool {
+        self.pressed_keys
+            .iter()
+            .any(|pressed_key| &pressed_key.key == key)
+    }
+
+    /// Forgets every pressed key that is not in `keep` and returns them so they can be released
+    pub fn release_except(&mut self, keep: &[EnigoKey]) -> Vec<EnigoKey> {
+        let mut release_keys: Vec<EnigoKey> = Vec::new();
+        self.pressed_keys.retain(|pressed_key| {
+            if keep.contains(&pressed_key.key) {
+                return true;
+            }
+            release_keys.push(pressed_key.key.clone());
+            false
+        });
+        release_keys
+    }
+
     pub fn time_update(&mut self) -> Vec<EnigoKey> {
         let mut release_keys: Vec<EnigoKey> = Vec::new();
         self.pressed_keys = self
@@ -269,3 +515,73 @@ impl KeysManager {
         self.pressed_keys = Vec::new();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_keycode_maps_to_an_enigo_key() {
+        for keycode in scancode::all_keycodes() {
+            assert!(
+                device_query_keycode_to_enigo_key(&keycode).is_ok(),
+                "{} has no enigo key",
+                keycode
+            );
+        }
+    }
+
+    #[test]
+    fn keycodes_map_to_enigo_keys() {
+        let cases = [
+            (Keycode::A, Key::Unicode('a')),
+            (Keycode::Key0, Key::Unicode('0')),
+            (Keycode::F20, Key::F20),
+            (Keycode::LControl, Key::LControl),
+            (Keycode::RShift, Key::RShift),
+            (Keycode::LAlt, Key::Alt),
+            (Keycode::LOption, Key::Alt),
+            (Keycode::LMeta, Key::Meta),
+            (Keycode::Command, Key::Meta),
+            (Keycode::RMeta, right_meta()),
+            (Keycode::RAlt, right_alt()),
+            (Keycode::Enter, Key::Return),
+            (Keycode::NumpadEnter, Key::Return),
+            (Keycode::Insert, insert()),
+            (Keycode::Slash, Key::Unicode('/')),
+        ];
+        for (keycode, key) in cases {
+            assert_eq!(
+                device_query_keycode_to_enigo_key(&keycode).ok(),
+                Some(key),
+                "{}",
+                keycode
+            );
+        }
+    }
+
+    #[test]
+    fn composed_keys_type_the_us_character() {
+        assert_eq!(keycode_to_character(&Keycode::Slash, false), Some('/'));
+        assert_eq!(keycode_to_character(&Keycode::Slash, true), Some('?'));
+        assert_eq!(keycode_to_character(&Keycode::Numpad5, true), Some('5'));
+        assert_eq!(keycode_to_character(&Keycode::A, false), None);
+    }
+
+    #[test]
+    fn mouse_buttons_map_to_enigo_buttons() {
+        assert_eq!(
+            mouse_button_to_enigo_mouse_button(1).ok(),
+            Some(Button::Left)
+        );
+        assert_eq!(
+            mouse_button_to_enigo_mouse_button(2).ok(),
+            Some(Button::Right)
+        );
+        assert_eq!(
+            mouse_button_to_enigo_mouse_button(3).ok(),
+            Some(Button::Middle)
+        );
+        assert!(mouse_button_to_enigo_mouse_button(0).is_err());
+    }
+}
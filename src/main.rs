@@ -1,20 +1,41 @@
-use std::{net::SocketAddrV4, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use control::ControlCommand;
+use display::{Client, Edge};
 use gui::GUI;
 use protocol::EventHandler;
 use tokio::{runtime::Handle, sync::Mutex};
 
+mod calibration;
 mod communicate;
+mod config;
+mod control;
 mod display;
+mod file_transfer;
 mod gui;
+mod hotkeys;
 mod input;
 mod key_handler;
 mod mouse_handler;
 mod protocol;
+mod remap;
+mod scancode;
+mod switch_guard;
 use std::env;
 
 const GROUP_ID_PORT: &str = "225.0.4.16:31725";
 const SENDER_PORT: u16 = 31726;
+const FILE_TRANSFER_PORT: u16 = 31727;
+const CONTROL_PORT: u16 = 31728;
+const DISPLAY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often positions of a mismatching layout may trigger a layout or display sync
+const LAYOUT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
 
 struct ClientUpdates {
     displays: Arc<Mutex<display::DisplayManager>>,
@@ -26,6 +47,19 @@ impl communicate::ClientUpdates for ClientUpdates {
     async fn update(&self, devices: &Vec<communicate::ReceiverDevice>) {
         let lock = self.displays.lock().await;
         let missing_displays = lock.get_missing_displays(devices);
+        if !missing_displays.is_empty() {
+            //new peers have to learn the layout before they can interpret our positions
+            match self
+                .event_handler
+                .emit_event(Box::new(lock.layout().clone()))
+                .await
+            {
+                Ok(_) => {}
+                Err(e) => {
+                    println!("Error sharing layout: {}", e);
+                }
+            }
+        }
         for device in missing_displays {
             match self
                 .event_handler
@@ -43,6 +77,301 @@ impl communicate::ClientUpdates for ClientUpdates {
     }
 }
 
+struct ControlCommands {
+    mouse_handler: Arc<Mutex<mouse_handler::Handler>>,
+    event_handler: Arc<EventHandler>,
+    file_transfer: Arc<file_transfer::FileTransfer>,
+    config: Arc<Mutex<config::Config>>,
+    displays: Arc<Mutex<display::DisplayManager>>,
+    calibration: Mutex<Option<calibration::Calibration>>,
+}
+
+fn side_name(edge: &Edge) -> &'static str {
+    match edge {
+        Edge::Left => "left of",
+        Edge::Right => "right of",
+        Edge::Top => "above",
+        Edge::Bottom => "below",
+    }
+}
+
+impl ControlCommands {
+    /// Shares a changed layout with all peers and persists it
+    async fn share_layout(&self, layout: display::Layout) -> Result<(), String> {
+        if let Err(e) = self
+            .event_handler
+            .emit_event(Box::new(layout.clone()))
+            .await
+        {
+            return Err(format!("unable to share the layout: {}", e));
+        }
+        let mut config = self.config.lock().await;
+        match config.save_layout(layout) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("unable to save the layout: {}", e)),
+        }
+    }
+
+    async fn start_calibration(&self) -> Result<String, String> {
+        let peers = self.displays.lock().await.get_peer_identifiers();
+        if peers.is_empty() {
+            return Err(String::from("There are no peers to calibrate"));
+        }
+        {
+            let mut mouse_handler = self.mouse_handler.lock().await;
+            mouse_handler.set_locked(false);
+            if let Err(e) = mouse_handler.jump_to_client(&Client::IsSelf).await {
+                return Err(format!("Unable to keep the cursor on this machine: {}", e));
+            }
+            mouse_handler.set_locked(true);
+        }
+
+        let mut response = String::from("Machines:");
+        for (index, (client, name)) in peers.iter().enumerate() {
+            let number = index as u32 + 1;
+            response += &format!("\n{}: {}", number, name);
+            if let Client::IsNetworked(addr) = client {
+                if let Err(e) = self
+                    .event_handler
+                    .specific_communication(*addr, Box::new(protocol::Identify { number }))
+                    .await
+                {
+                    response += &format!(" (unable to show the number: {})", e);
+                }
+            }
+        }
+        *self.calibration.lock().await = Some(calibration::Calibration::new(
+            peers.into_iter().map(|v| v.1).collect(),
+        ));
+        Ok(response)
+    }
+
+    async fn finish_calibration(&self) -> Result<String, String> {
+        let calibration = match self.calibration.lock().await.take() {
+            Some(v) => v,
+            None => return Err(String::from("No calibration is running")),
+        };
+        let layout = {
+            let displays = self.displays.lock().await;
+            let own = match displays.get_own_identifier() {
+                Some(v) => v,
+                None => return Err(String::from("Unable to name this machine")),
+            };
+            calibration.layout(own, displays.layout())
+        };
+
+        let new_layout = layout.clone();
+        let mut mouse_handler = self.mouse_handler.lock().await;
+        let applied = mouse_handler
+            .change_layout(|v| v.set_layout(new_layout))
+            .await;
+        mouse_handler.set_locked(false);
+        drop(mouse_handler);
+        if let Err(e) = applied {
+            return Err(format!("Unable to apply the layout: {}", e));
+        }
+        match self.share_layout(layout).await {
+            Ok(_) => Ok(String::from("Saved the calibrated layout")),
+            Err(e) => Err(format!("Applied the calibrated layout but {}", e)),
+        }
+    }
+
+    async fn send_file(&self, target: Ipv4Addr, path: &Path) -> String {
+        match self.file_transfer.send_file(target, path).await {
+            Ok(_) => format!("Sent {} to {}", path.display(), target),
+            Err(e) => format!("Unable to send {}: {}", path.display(), e),
+        }
+    }
+
+    async fn current_target(&self) -> Result<SocketAddrV4, String> {
+        let target = match self
+            .mouse_handler
+            .lock()
+            .await
+            .get_local_mouse_position()
+            .await
+        {
+            Ok(v) => v.client,
+            Err(e) => return Err(format!("Unable to find the current client: {}", e)),
+        };
+        match target {
+            Client::IsNetworked(v) => Ok(v),
+            Client::IsSelf => Err(String::from(
+                "The cursor is on this machine. Move it to the target first.",
+            )),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl control::ControlHandler for ControlCommands {
+    async fn handle(&self, command: ControlCommand) -> String {
+        match command {
+            ControlCommand::SendFile(path) => {
+                let addr = match self.current_target().await {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
+                self.send_file(*addr.ip(), &path).await
+            }
+            ControlCommand::DropFiles(paths) => {
+                let addr = match self.mouse_handler.lock().await.finish_drag().await {
+                    Ok(v) => v,
+                    Err(e) => return format!("Unable to follow the drag: {}", e),
+                };
+                let mut responses = Vec::new();
+                for path in paths {
+                    responses.push(self.send_file(*addr.ip(), &path).await);
+                }
+                responses.join("\n")
+            }
+            ControlCommand::SendText(text) => {
+                let addr = match self.current_target().await {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
+                for part in input::TextInput::split(&text) {
+                    if let Err(e) = self
+                        .event_handler
+                        .specific_communication(addr, Box::new(part))
+                        .await
+                    {
+                        return format!("Unable to send text: {}", e);
+                    }
+                }
+                format!("Sent text to {}", addr.ip())
+            }
+            ControlCommand::ToggleLock => {
+                let mut mouse_handler = self.mouse_handler.lock().await;
+                let locked = !mouse_handler.is_locked();
+                mouse_handler.set_locked(locked);
+                if locked {
+                    String::from("Cursor is locked to the current machine")
+                } else {
+                    String::from("Cursor is unlocked")
+                }
+            }
+            ControlCommand::Place(client, edge, neighbour) => {
+                let placed = self
+                    .mouse_handler
+                    .lock()
+                    .await
+                    .change_layout(|v| v.place(&client, edge, &neighbour))
+                    .await;
+                let layout = match placed {
+                    Ok(Ok(v)) => v,
+                    Ok(Err(e)) => return format!("Unable to place {}: {}", client, e),
+                    Err(e) => return format!("Unable to place {}: {}", client, e),
+                };
+                match self.share_layout(layout).await {
+                    Ok(_) => format!("Placed {} next to {}", client, neighbour),
+                    Err(e) => format!("Placed {} but {}", client, e),
+                }
+            }
+            ControlCommand::StartCalibration => match self.start_calibration().await {
+                Ok(v) => v,
+                Err(e) => e,
+            },
+            ControlCommand::CalibrateMachine(number) => {
+                let position = self.mouse_handler.lock().await.get_current_position();
+                let edge = match self.displays.lock().await.get_pushed_edge(&position) {
+                    Some(v) => v,
+                    None => return String::from("Unable to tell which edge the cursor is at"),
+                };
+                match self.calibration.lock().await.as_mut() {
+                    Some(calibration) => match calibration.record(number, edge) {
+                        Some(name) => format!("{} sits {} this machine", name, side_name(&edge)),
+                        None => format!("There is no machine with the number {}", number),
+                    },
+                    None => String::from("No calibration is running"),
+                }
+            }
+            ControlCommand::FinishCalibration => match self.finish_calibration().await {
+                Ok(v) => v,
+                Err(e) => e,
+            },
+            ControlCommand::Snapshot => {
+                let (cursor, locked) = {
+                    let mouse_handler = self.mouse_handler.lock().await;
+                    (
+                        mouse_handler.get_current_position(),
+                        mouse_handler.is_locked(),
+                    )
+                };
+                let snapshot = self.displays.lock().await.snapshot(&cursor, locked);
+                match serde_json::to_string_pretty(&snapshot) {
+                    Ok(v) => v,
+                    Err(e) => format!("Unable to serialize the layout: {}", e),
+                }
+            }
+            ControlCommand::SetAlignment(from, to, alignment) => {
+                let changed = self
+                    .mouse_handler
+                    .lock()
+                    .await
+                    .change_layout(|v| v.set_alignment(&from, &to, alignment))
+                    .await;
+                let layout = match changed {
+                    Ok(Ok(v)) => v,
+                    Ok(Err(e)) => return format!("Unable to align {} and {}: {}", from, to, e),
+                    Err(e) => return format!("Unable to align {} and {}: {}", from, to, e),
+                };
+                match self.share_layout(layout).await {
+                    Ok(_) => format!("Aligned {} and {}", from, to),
+                    Err(e) => format!("Aligned {} and {} but {}", from, to, e),
+                }
+            }
+            ControlCommand::Status => {
+                let mut mouse_handler = self.mouse_handler.lock().await;
+                let client = match mouse_handler.get_local_mouse_position().await {
+                    Ok(v) => match v.client {
+                        Client::IsSelf => String::from("this machine"),
+                        Client::IsNetworked(addr) => addr.ip().to_string(),
+                    },
+                    Err(e) => format!("unknown ({})", e),
+                };
+                format!(
+                    "Cursor: {}\nLocked: {}",
+                    client,
+                    if mouse_handler.is_locked() {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                )
+            }
+        }
+    }
+}
+
+async fn run_command(command: ControlCommand) {
+    match control::send_command(CONTROL_PORT, &command).await {
+        Ok(v) => println!("{}", v),
+        Err(e) => println!("{}", e),
+    }
+}
+
+/// Asks for the position of every numbered machine until the user is done
+async fn calibrate() {
+    run_command(ControlCommand::StartCalibration).await;
+    println!("Push the cursor against the edge of this screen that faces a machine, keep it there and enter the number shown on that machine. Enter nothing when you are done.");
+    loop {
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).is_err() {
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        match line.parse::<usize>() {
+            Ok(number) => run_command(ControlCommand::CalibrateMachine(number)).await,
+            Err(_) => println!("{} is not a number", line),
+        }
+    }
+    run_command(ControlCommand::FinishCalibration).await;
+}
+
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = env::args().collect();
@@ -58,13 +387,84 @@ async fn main() {
     match args.get(1) {
         Some(v) => {
             if v == "gui" {
-                GUI::new();
+                match (args.get(2).map(|v| v.as_str()), args.get(3)) {
+                    (Some("--identify"), Some(number)) => {
+                        GUI::identify(number.parse().unwrap_or(0));
+                        return;
+                    }
+                    (Some("--drop"), Some(x)) => {
+                        let y = args.get(4).and_then(|v| v.parse().ok()).unwrap_or(0);
+                        GUI::catch_drop(x.parse().unwrap_or(0), y, CONTROL_PORT);
+                        return;
+                    }
+                    (Some("--settings"), _) => {
+                        GUI::settings(CONTROL_PORT);
+                        return;
+                    }
+                    _ => {
+                        GUI::new();
+                    }
+                }
+            } else if v == "calibrate" {
+                calibrate().await;
+                return;
+            } else if v == "send-file" {
+                match args.get(2) {
+                    Some(path) => {
+                        let path = std::fs::canonicalize(path).unwrap_or(PathBuf::from(path));
+                        run_command(ControlCommand::SendFile(path)).await;
+                    }
+                    None => println!("Usage: seamless send-file <path>"),
+                }
+                return;
+            } else if v == "lock" {
+                run_command(ControlCommand::ToggleLock).await;
+                return;
+            } else if v == "status" {
+                match args.get(2).map(|v| v.as_str()) {
+                    Some("--json") => run_command(ControlCommand::Snapshot).await,
+                    _ => run_command(ControlCommand::Status).await,
+                }
+                return;
+            } else if v == "place" {
+                let edge = match args.get(3).map(|v| v.as_str()) {
+                    Some("left") => Some(Edge::Left),
+                    Some("right") => Some(Edge::Right),
+                    Some("above") => Some(Edge::Top),
+                    Some("below") => Some(Edge::Bottom),
+                    _ => None,
+                };
+                match (args.get(2), edge, args.get(4)) {
+                    (Some(client), Some(edge), Some(neighbour)) => {
+                        run_command(ControlCommand::Place(
+                            client.clone(),
+                            edge,
+                            neighbour.clone(),
+                        ))
+                        .await
+                    }
+                    _ => println!(
+                        "Usage: seamless place <machine> <left|right|above|below> <machine>"
+                    ),
+                }
+                return;
+            } else if v == "send-text" {
+                match args.get(2) {
+                    Some(_) => run_command(ControlCommand::SendText(args[2..].join(" "))).await,
+                    None => println!("Usage: seamless send-text <text>"),
+                }
+                return;
             }
         }
         None => {}
     }
 
-    let displays = Arc::new(Mutex::new(display::DisplayManager::new().unwrap()));
+    let config = config::Config::load_or_default();
+    let shared_config = Arc::new(Mutex::new(config.clone()));
+
+    let displays = Arc::new(Mutex::new(
+        display::DisplayManager::new(&config.name, config.layout.clone()).unwrap(),
+    ));
     let comms = Arc::new(
         communicate::Communicate::new(
             SocketAddrV4::from_str(GROUP_ID_PORT).unwrap(),
@@ -83,78 +483,168 @@ async fn main() {
     };
     let prot2 = prot.clone();
     let prot3 = prot.clone();
+    let prot4 = prot.clone();
     let disp2 = displays.clone();
 
     let (mut gui_process_manager, gui_handler) = gui::GUIProcessManager::new(own_path.clone());
+    let gui_handler = Arc::new(gui_handler);
+    let gui_handler2 = gui_handler.clone();
 
     let handler = Arc::new(Mutex::new(mouse_handler::Handler::new(
         prot.clone(),
         displays.clone(),
-        Arc::new(gui_handler),
+        gui_handler,
+        config.switch_policies.clone(),
     )));
     let handler2 = handler.clone();
 
+    let file_transfer = Arc::new(file_transfer::FileTransfer::new(
+        FILE_TRANSFER_PORT,
+        config.receive_directory.clone(),
+        config.max_file_size,
+        comms.clone(),
+    ));
+    let file_transfer2 = file_transfer.clone();
+    tokio::spawn(async move {
+        if let Err(e) = file_transfer2.listen().await {
+            println!("Unable to receive files: {}", e);
+        }
+    });
+
+    match control::ControlServer::new(CONTROL_PORT).await {
+        Ok(server) => {
+            let control_commands = Arc::new(ControlCommands {
+                mouse_handler: handler.clone(),
+                event_handler: prot.clone(),
+                file_transfer,
+                config: shared_config.clone(),
+                displays: displays.clone(),
+                calibration: Mutex::new(None),
+            });
+            tokio::spawn(async move {
+                server.listen(control_commands).await;
+            });
+        }
+        Err(e) => println!("Unable to start control server: {}", e),
+    }
+
     comms.assign_updates(Box::new(client_updates)).await;
 
     let key_handler = Arc::new(Mutex::new(key_handler::Handler::new()));
 
     let key_handler2 = key_handler.clone();
 
+    let own_name = config.name.clone();
+    let last_layout_sync: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
     tokio::spawn(async move {
         prot.event_listener(move |v| match v {
             protocol::Events::ClientDisplays(v) => {
-                let disp = disp2.clone();
+                let handler = handler2.clone();
                 tokio::spawn(async move {
-                    let mut lock = disp.lock().await;
-                    match lock.received_displays(v) {
+                    match handler
+                        .lock()
+                        .await
+                        .change_layout(|lock| lock.received_displays(v))
+                        .await
+                    {
+                        Ok(Err(e)) => {
+                            println!("Unable to add received display: {}", e);
+                        }
                         Err(e) => {
                             println!("Unable to add received display: {}", e);
                         }
                         _ => {}
                     };
-                    println!("{:?}", lock);
                 });
             }
             protocol::Events::MouseMovement(v) => {
                 let handler = handler2.clone();
+                let disp = disp2.clone();
+                let prot = prot2.clone();
+                let last_layout_sync = last_layout_sync.clone();
+                let own_name = own_name.clone();
                 tokio::spawn(async move {
-                    match handler.lock().await.set_current_position(v).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("Error setting current position: {}", e)
+                    let (layout, displays_hash) = {
+                        let disp = disp.lock().await;
+                        (disp.layout().clone(), disp.displays_hash())
+                    };
+                    let version_differs =
+                        matches!(v.layout_version, Some(version) if version != layout.version);
+                    let displays_differ =
+                        matches!(v.displays_hash, Some(hash) if hash != displays_hash);
+                    if !version_differs && !displays_differ {
+                        match handler.lock().await.set_current_position(v.position).await {
+                            Ok(_) => {}
+                            Err(e) => {
+                                println!("Error setting current position: {}", e)
+                            }
+                        }
+                        return;
+                    }
+
+                    //every following position has the same mismatch, one sync per interval is enough
+                    {
+                        let mut last_layout_sync = last_layout_sync.lock().await;
+                        if matches!(*last_layout_sync, Some(v) if v.elapsed() < LAYOUT_SYNC_INTERVAL)
+                        {
+                            return;
+                        }
+                        *last_layout_sync = Some(Instant::now());
+                    }
+                    println!(
+                        "Ignoring positions of layout version {:?}, own version is {}",
+                        v.layout_version, layout.version
+                    );
+                    if version_differs {
+                        //whoever has the older layout adopts the other one
+                        if let Err(e) = prot.emit_event(Box::new(layout)).await {
+                            println!("Error sharing layout: {}", e)
                         }
+                        return;
+                    }
+                    //same layout but different displays, both sides refresh the displays of the other
+                    if let Some(src) = v.src {
+                        if let Err(e) = prot
+                            .emit_event(Box::new(protocol::RequestDisplays {
+                                client_ip: *src.ip(),
+                            }))
+                            .await
+                        {
+                            println!("Error requesting display update: {}", e)
+                        }
+                    }
+                    match display::ClientDisplays::new_local(&own_name) {
+                        Ok(own_displays) => {
+                            if let Err(e) = prot.emit_event(Box::new(own_displays)).await {
+                                println!("Error sharing displays: {}", e)
+                            }
+                        }
+                        Err(e) => println!("Error generating own display: {}", e),
                     }
                 });
             }
             protocol::Events::RequestDisplays(v) => {
                 let comms = comms.clone();
                 let prot = prot2.clone();
+                let own_name = own_name.clone();
                 println!("got display request");
                 tokio::spawn(async move {
-                    let own_ip = comms.get_own_ip().await;
-                    match own_ip {
-                        Some(v2) => {
-                            if v2.ip() == &v.client_ip {
-                                let own_displays = match display::ClientDisplays::new_local() {
-                                    Ok(v) => v,
-                                    Err(e) => {
-                                        println!("Error generating own display: {}", e);
-                                        return;
-                                    }
-                                };
-                                match prot.emit_event(Box::new(own_displays)).await {
-                                    Err(e) => {
-                                        println!("Unable to send own display: {}", e)
-                                    }
-                                    _ => {}
-                                }
-                            }
+                    //while the own ip is unknown the request might be for us, answering it is harmless
+                    if comms.get_own_ip().await.is_some() && !comms.is_own_ip(&v.client_ip).await {
+                        return;
+                    }
+                    let own_displays = match display::ClientDisplays::new_local(&own_name) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            println!("Error generating own display: {}", e);
+                            return;
                         }
-                        None => {
-                            println!(
-                                "Unable to handle own display request because own ip is unknown"
-                            );
+                    };
+                    match prot.emit_event(Box::new(own_displays)).await {
+                        Err(e) => {
+                            println!("Unable to send own display: {}", e)
                         }
+                        _ => {}
                     }
                 });
             }
@@ -169,6 +659,60 @@ async fn main() {
                     }
                 });
             }
+            protocol::Events::KeyState(state) => {
+                let key_handler = key_handler2.clone();
+                tokio::spawn(async move {
+                    match key_handler.lock().await.received_key_state(state) {
+                        Err(e) => {
+                            println!("Error applying key state: {}", e)
+                        }
+                        _ => {}
+                    }
+                });
+            }
+            protocol::Events::Layout(layout) => {
+                let handler = handler2.clone();
+                let disp = disp2.clone();
+                let prot = prot2.clone();
+                let config = shared_config.clone();
+                tokio::spawn(async move {
+                    let current = disp.lock().await.layout().clone();
+                    if current.supersedes(&layout) {
+                        if let Err(e) = prot.emit_event(Box::new(current)).await {
+                            println!("Error sharing layout: {}", e)
+                        }
+                        return;
+                    }
+                    if !layout.supersedes(&current) {
+                        return;
+                    }
+                    println!("Adopting layout version {}", layout.version);
+                    let new_layout = layout.clone();
+                    if let Err(e) = handler
+                        .lock()
+                        .await
+                        .change_layout(|v| v.set_layout(new_layout))
+                        .await
+                    {
+                        println!("Error applying layout: {}", e)
+                    }
+                    if let Err(e) = config.lock().await.save_layout(layout) {
+                        println!("Unable to save layout: {}", e)
+                    }
+                });
+            }
+            protocol::Events::Identify(v) => match gui_handler2.identify(v.number) {
+                Err(e) => {
+                    println!("Unable to show calibration number: {}", e)
+                }
+                _ => {}
+            },
+            protocol::Events::TextInput(input) => {
+                let key_handler = key_handler2.clone();
+                tokio::spawn(async move {
+                    key_handler.lock().await.received_text(input);
+                });
+            }
         })
         .await;
     });
@@ -178,7 +722,14 @@ async fn main() {
     let _gld = mouse_input.mouse_movement_listener(handler3, Handle::current());
 
     let handler4 = handler.clone();
-    let key_input = input::KeyInputReceiver::new(prot3, handler4);
+    let key_sender = Arc::new(input::KeySender::new(
+        prot3,
+        remap::Remapper::new(config.key_remaps.clone()),
+        hotkeys::Hotkeys::new(config.hotkeys.clone()),
+        config.key_transport,
+    ));
+    handler4.lock().await.assign_handoff(key_sender.clone());
+    let key_input = input::KeyInputReceiver::new(key_sender, handler4);
     let _gld2 = key_input.key_input_listener(Handle::current());
 
     tokio::spawn(async move {
@@ -190,5 +741,58 @@ async fn main() {
         }
     });
 
+    let handler5 = handler.clone();
+    tokio::spawn(async move {
+        loop {
+            {
+                match handler5.lock().await.timed_update().await {
+                    Err(e) => {
+                        println!("Error finishing edge switch: {}", e)
+                    }
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+    });
+
+    let handler6 = handler.clone();
+    let disp3 = displays.clone();
+    let own_name = config.name.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(DISPLAY_POLL_INTERVAL).await;
+            let own_displays = match display::ClientDisplays::new_local(&own_name) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Error generating own display: {}", e);
+                    continue;
+                }
+            };
+            if !disp3.lock().await.own_displays_changed(&own_displays) {
+                continue;
+            }
+            println!("Own displays changed");
+            let new_displays = own_displays.clone();
+            match handler6
+                .lock()
+                .await
+                .change_layout(|v| v.update_own_displays(new_displays))
+                .await
+            {
+                Err(e) => {
+                    println!("Error applying own displays: {}", e)
+                }
+                _ => {}
+            }
+            match prot4.emit_event(Box::new(own_displays)).await {
+                Err(e) => {
+                    println!("Unable to send own display: {}", e)
+                }
+                _ => {}
+            }
+        }
+    });
+
     gui_process_manager.listen().await;
 }
@@ -1,23 +1,32 @@
 use {
     crate::{
-        display::{Client, ClientMousePosition, DisplayError, DisplayManager, Edge},
-        gui::GUIHandler,
-        input::{MouseMovement, MousePosition},
-        protocol::{EventHandler, ProtocolError},
+        display::{Client, ClientMousePosition, DisplayError, DisplayManager, Edge, OsFamily},
+        gui::{GUIHandler, GUIMessage},
+        hotkeys::HotkeyAction,
+        input::{Key, MouseMovement, MousePosition},
+        protocol::{EventHandler, GlobalPosition, ProtocolError},
+        switch_guard::{SwitchGuard, SwitchPolicies},
     },
-    enigo::{Enigo, MouseControllable},
-    std::{error, fmt, sync::Arc},
+    enigo::{Coordinate, Enigo, Mouse},
+    std::{error, fmt, net::SocketAddrV4, sync::Arc},
     tokio::sync::{mpsc, Mutex},
 };
 
 type Result<T> = std::result::Result<T, MouseHandlerError>;
 
+/// The button that drags files, held at an edge it opens the drop window instead of switching
+const DRAG_BUTTON: Key = Key::MouseButton(1);
+
 #[derive(Debug)]
 pub enum MouseHandlerError {
     DisplayError(DisplayError),
     ProtocolError(ProtocolError),
     SendError,
     OwnDisplayError,
+    UnknownClient,
+    Locked,
+    SwitchBlocked,
+    InputError(enigo::InputError),
 }
 
 pub struct MouseIndex {
@@ -42,6 +51,18 @@ impl fmt::Display for MouseHandlerError {
             MouseHandlerError::OwnDisplayError => {
                 write!(f, "Was unable to find own display")
             }
+            MouseHandlerError::UnknownClient => {
+                write!(f, "There is no connected client with that name")
+            }
+            MouseHandlerError::Locked => {
+                write!(f, "The cursor is locked to the current client")
+            }
+            MouseHandlerError::SwitchBlocked => {
+                write!(f, "The switch policy of this edge is not met yet")
+            }
+            MouseHandlerError::InputError(v) => {
+                write!(f, "Unable to move the cursor: {}", v)
+            }
         }
     }
 }
@@ -52,24 +73,52 @@ impl From<DisplayError> for MouseHandlerError {
     }
 }
 
+impl From<enigo::InputError> for MouseHandlerError {
+    fn from(value: enigo::InputError) -> Self {
+        MouseHandlerError::InputError(value)
+    }
+}
+
 impl From<ProtocolError> for MouseHandlerError {
     fn from(value: ProtocolError) -> Self {
         MouseHandlerError::ProtocolError(value)
     }
 }
 
-impl From<mpsc::error::SendError<bool>> for MouseHandlerError {
-    fn from(_value: mpsc::error::SendError<bool>) -> Self {
+impl From<mpsc::error::SendError<GUIMessage>> for MouseHandlerError {
+    fn from(_value: mpsc::error::SendError<GUIMessage>) -> Self {
         MouseHandlerError::SendError
     }
 }
 
+/// Notified whenever the client under the cursor changes
+#[async_trait::async_trait]
+pub trait Handoff
+where
+    Self: Sync + Send,
+{
+    async fn handoff(&self, client: &Client, os: Option<OsFamily>);
+}
+
 pub struct Handler {
     event_handler: Arc<EventHandler>,
     enigo: Enigo,
     display_manager: Arc<Mutex<DisplayManager>>,
     current_position: MousePosition,
+    current_client: Option<Client>,
+    /// Keeps the cursor on the current client while set
+    locked: bool,
+    switch_guard: SwitchGuard,
+    /// The position a blocked switch would have moved to. Taken once the guard is satisfied.
+    pending_switch: Option<MousePosition>,
     gui_handler: Arc<GUIHandler>,
+    handoff: Option<Arc<dyn Handoff>>,
+    /// Whether `DRAG_BUTTON` is held on this machine
+    dragging: bool,
+    /// Whether the drop window was opened for the current drag
+    catching_drop: bool,
+    /// The peer beyond the edge a drag was held at and where the cursor enters it
+    drop_target: Option<(SocketAddrV4, MousePosition)>,
 }
 
 impl Handler {
@@ -77,14 +126,76 @@ impl Handler {
         event_handler: Arc<EventHandler>,
         display_manager: Arc<Mutex<DisplayManager>>,
         gui_handler: Arc<GUIHandler>,
+        switch_policies: SwitchPolicies,
     ) -> Handler {
         Handler {
             event_handler,
             enigo: Enigo::new(&enigo::Settings::default()).unwrap(),
             display_manager,
             current_position: MousePosition { x: 0, y: 0 },
+            current_client: None,
+            locked: false,
+            switch_guard: SwitchGuard::new(switch_policies),
+            pending_switch: None,
             gui_handler,
+            handoff: None,
+            dragging: false,
+            catching_drop: false,
+            drop_target: None,
+        }
+    }
+
+    pub fn assign_handoff(&mut self, handoff: Arc<dyn Handoff>) {
+        self.handoff = Some(handoff);
+    }
+
+    /// Updates the keys held on this machine, which switch policies can require
+    pub fn set_held_keys(&mut self, keys: Vec<Key>) {
+        self.dragging = keys.contains(&DRAG_BUTTON);
+        if !self.dragging {
+            self.catching_drop = false;
+        }
+        self.switch_guard.set_held_keys(keys);
+    }
+
+    /// Moves the cursor onto the peer a drag was held at and returns it, the files dropped into
+    /// the drop window go there
+    pub async fn finish_drag(&mut self) -> Result<SocketAddrV4> {
+        let (addr, position) = match self.drop_target.take() {
+            Some(v) => v,
+            None => return Err(MouseHandlerError::UnknownClient),
+        };
+        if self.locked {
+            return Err(MouseHandlerError::Locked);
         }
+        self.set_current_position(position).await?;
+        self.emit_position().await?;
+        Ok(addr)
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    async fn update_client(&mut self, client: &Client) {
+        if self.current_client.as_ref() == Some(client) {
+            return;
+        }
+        self.current_client = Some(client.clone());
+
+        let handoff = match &self.handoff {
+            Some(v) => v.clone(),
+            None => return,
+        };
+        let os = match client {
+            Client::IsNetworked(v) => self.display_manager.lock().await.get_client_os(v),
+            Client::IsSelf => None,
+        };
+        handoff.handoff(client, os).await;
     }
 
     pub async fn mouse_movement(&mut self, mouse_position: MousePosition) -> Result<()> {
@@ -92,6 +203,8 @@ impl Handler {
 
         let mut new_global_position;
         let new_local_position_res;
+        // the edge the cursor left through, the landing spot can lie in any direction
+        let mut crossed_edge: Option<Edge> = None;
 
         {
             let lock = self.display_manager.lock().await;
@@ -103,12 +216,22 @@ impl Handler {
                             new_global_position = lock.get_global_mouse_position(mouse_position)?;
                         }
                         Client::IsNetworked(_) => {
-                            let display_size = self.enigo.main_display_size();
+                            //the movement is measured on the primary display, the layout is in logical pixels
+                            let display_size = self.enigo.main_display()?;
+                            let scale = lock.get_own_scale_factor();
                             new_global_position = self.current_position.clone()
                                 + MouseMovement {
-                                    x: (mouse_position.x - display_size.0 / 2) * 2,
-                                    y: (mouse_position.y - display_size.1 / 2) * 2,
+                                    x: (((mouse_position.x - display_size.0 / 2) * 2) as f32
+                                        / scale) as i32,
+                                    y: (((mouse_position.y - display_size.1 / 2) * 2) as f32
+                                        / scale) as i32,
                                 };
+                            if let Some((v, edge)) =
+                                lock.cross(&self.current_position, &new_global_position)
+                            {
+                                new_global_position = v;
+                                crossed_edge = Some(edge);
+                            }
                         }
                     }
                     last_local_position = Some(last_position);
@@ -122,6 +245,7 @@ impl Handler {
             let computed_local_position_res = lock.get_local_mouse_position(&new_global_position);
             new_local_position_res = match &computed_local_position_res {
                 Ok(v) => match &v.client {
+                    _ if self.locked => computed_local_position_res,
                     Client::IsSelf => {
                         let own_index = match lock.get_own_client_displays_index() {
                             Some(v) => v,
@@ -131,13 +255,36 @@ impl Handler {
                         };
 
                         match lock.is_on_edge(&v.mouse_position, own_index)? {
-                            Some(Edge::Left) => {
-                                new_global_position.x -= 5;
-                                lock.get_local_mouse_position(&new_global_position)
-                            }
-                            Some(Edge::Right) => {
-                                new_global_position.x += 5;
-                                lock.get_local_mouse_position(&new_global_position)
+                            Some(edge) => {
+                                let beyond = new_global_position.clone() + edge.step();
+                                let crossed = match lock.cross(&new_global_position, &beyond) {
+                                    Some((v, _)) => v,
+                                    None => beyond,
+                                };
+                                let crossed_local_res = lock.get_local_mouse_position(&crossed);
+                                match &crossed_local_res {
+                                    //a drag stays at the edge, files can only cross through the drop window
+                                    Ok(ClientMousePosition {
+                                        client: Client::IsNetworked(addr),
+                                        ..
+                                    }) if self.dragging => {
+                                        if !self.catching_drop {
+                                            let scale = lock.get_own_scale_factor();
+                                            self.gui_handler.catch_drop(MousePosition {
+                                                x: (v.mouse_position.x as f32 / scale) as i32,
+                                                y: (v.mouse_position.y as f32 / scale) as i32,
+                                            })?;
+                                            self.catching_drop = true;
+                                        }
+                                        self.drop_target = Some((*addr, crossed));
+                                        computed_local_position_res
+                                    }
+                                    _ => {
+                                        crossed_edge = Some(edge);
+                                        new_global_position = crossed;
+                                        crossed_local_res
+                                    }
+                                }
                             }
                             None => computed_local_position_res,
                         }
@@ -148,6 +295,31 @@ impl Handler {
             }
         }
 
+        let new_local_position_res = match (new_local_position_res, &last_local_position) {
+            (Ok(v), Some(last)) if self.locked && v.client != last.client => {
+                Err(MouseHandlerError::Locked)
+            }
+            (Ok(v), Some(last)) if v.client != last.client => {
+                let edge = match crossed_edge {
+                    Some(v) => v,
+                    None => Edge::between(&self.current_position, &new_global_position),
+                };
+                if self.switch_guard.attempt(edge) {
+                    self.switch_guard.switched();
+                    self.pending_switch = None;
+                    Ok(v)
+                } else {
+                    self.pending_switch = Some(new_global_position.clone());
+                    Err(MouseHandlerError::SwitchBlocked)
+                }
+            }
+            (v, _) => {
+                self.switch_guard.released();
+                self.pending_switch = None;
+                v.map_err(MouseHandlerError::from)
+            }
+        };
+
         match new_local_position_res {
             Ok(new_local_position) => {
                 match new_local_position.client {
@@ -159,6 +331,7 @@ impl Handler {
                         self.gui_handler.quit_ui()?;
                     }
                 }
+                self.update_client(&new_local_position.client).await;
                 self.current_position = new_global_position;
             }
             Err(_e) => match last_local_position {
@@ -172,17 +345,52 @@ impl Handler {
             },
         }
 
+        self.emit_position().await?;
+
+        Ok(())
+    }
+
+    /// Finishes a switch that was waiting for the dwell time of its edge
+    pub async fn timed_update(&mut self) -> Result<()> {
+        let target = match &self.pending_switch {
+            Some(v) => v.clone(),
+            None => return Ok(()),
+        };
+        if self.locked || !self.switch_guard.ready() {
+            return Ok(());
+        }
+        self.switch_guard.switched();
+        self.pending_switch = None;
+
+        self.set_current_position(target).await?;
+        self.emit_position().await?;
+        Ok(())
+    }
+
+    /// Shares the current position with all peers, tagged with the layout it refers to
+    async fn emit_position(&self) -> Result<()> {
+        let (layout_version, displays_hash) = {
+            let display_manager = self.display_manager.lock().await;
+            (
+                display_manager.layout().version,
+                display_manager.displays_hash(),
+            )
+        };
         self.event_handler
-            .emit_event(Box::new(self.current_position.clone()))
+            .emit_event(Box::new(GlobalPosition {
+                position: self.current_position.clone(),
+                layout_version: Some(layout_version),
+                displays_hash: Some(displays_hash),
+                src: None,
+            }))
             .await?;
-
         Ok(())
     }
 
     fn center_mouse_init_gui(&mut self) -> Result<()> {
-        let display_size = self.enigo.main_display_size();
+        let display_size = self.enigo.main_display()?;
         self.enigo
-            .mouse_move_to(display_size.0 / 2, display_size.1 / 2);
+            .move_mouse(display_size.0 / 2, display_size.1 / 2, Coordinate::Abs)?;
         self.gui_handler.init_ui()?;
         Ok(())
     }
@@ -200,20 +408,51 @@ impl Handler {
 
         match new_position.client {
             Client::IsSelf => {
-                self.enigo
-                    .mouse_move_to(new_position.mouse_position.x, new_position.mouse_position.y);
+                self.enigo.move_mouse(
+                    new_position.mouse_position.x,
+                    new_position.mouse_position.y,
+                    Coordinate::Abs,
+                )?;
                 self.gui_handler.quit_ui()?;
             }
             Client::IsNetworked(_) => {
                 self.gui_handler.init_ui()?;
-                let size = self.enigo.main_display_size();
-                self.enigo.mouse_move_to(size.0 / 2, size.1 / 2);
+                let size = self.enigo.main_display()?;
+                self.enigo
+                    .move_mouse(size.0 / 2, size.1 / 2, Coordinate::Abs)?;
             }
         }
+        self.update_client(&new_position.client).await;
 
         Ok(())
     }
 
+    /// Runs `change` on the layout and keeps the cursor at the same spot of the same client. If
+    /// its display is gone the cursor moves to the primary display of the client.
+    pub async fn change_layout<T>(
+        &mut self,
+        change: impl FnOnce(&mut DisplayManager) -> T,
+    ) -> Result<T> {
+        let result;
+        let mut display_gone = false;
+        {
+            let mut lock = self.display_manager.lock().await;
+            let local_position = lock.get_local_mouse_position(&self.current_position);
+            result = change(&mut lock);
+            if let Ok(v) = local_position {
+                display_gone = lock.get_client_global_mouse_position(&v).is_err();
+                if let Ok(v) = lock.reposition(&v) {
+                    self.current_position = v;
+                }
+            }
+        }
+        if display_gone {
+            self.apply_current_position().await?;
+        }
+        self.emit_position().await?;
+        Ok(result)
+    }
+
     pub async fn get_local_mouse_position(&mut self) -> Result<ClientMousePosition> {
         Ok(self
             .display_manager
@@ -222,6 +461,50 @@ impl Handler {
             .get_local_mouse_position(&self.current_position)?)
     }
 
+    pub async fn run_hotkey(&mut self, action: &HotkeyAction) -> Result<()> {
+        if action == &HotkeyAction::ToggleLock {
+            self.set_locked(!self.locked);
+            return Ok(());
+        }
+
+        let client = {
+            let lock = self.display_manager.lock().await;
+            let current_client = lock
+                .get_local_mouse_position(&self.current_position)?
+                .client;
+            match action {
+                HotkeyAction::SwitchTo(name) => lock.find_client(name),
+                HotkeyAction::Next => lock.get_neighbour_client(&current_client, 1),
+                HotkeyAction::Previous => lock.get_neighbour_client(&current_client, -1),
+                HotkeyAction::Home => Some(Client::IsSelf),
+                HotkeyAction::ToggleLock => None,
+            }
+        };
+        match client {
+            Some(v) => self.jump_to_client(&v).await,
+            None => Err(MouseHandlerError::UnknownClient),
+        }
+    }
+
+    /// Moves the cursor to the center of the primary display of `client`
+    pub async fn jump_to_client(&mut self, client: &Client) -> Result<()> {
+        if self.locked {
+            return Err(MouseHandlerError::Locked);
+        }
+        let position = self
+            .display_manager
+            .lock()
+            .await
+            .get_client_center(client)?;
+        self.set_current_position(position).await?;
+        self.emit_position().await?;
+        Ok(())
+    }
+
+    pub fn get_current_position(&self) -> MousePosition {
+        self.current_position.clone()
+    }
+
     pub async fn set_current_position(&mut self, current_position: MousePosition) -> Result<()> {
         self.current_position = current_position;
         self.apply_current_position().await?;
@@ -1,8 +1,8 @@
 use {
     crate::{
         communicate::{Communicate, CommunicateError},
-        display::{Client, ClientDisplays},
-        input::{KeyInput, MousePosition},
+        display::{Client, ClientDisplays, Layout},
+        input::{KeyInput, KeyState, MousePosition, TextInput},
     },
     std::{
         error, fmt,
@@ -64,15 +64,79 @@ pub struct RequestDisplays {
     pub client_ip: Ipv4Addr,
 }
 
+/// Asks a peer to show its number during calibration
+#[derive(Debug)]
+pub struct Identify {
+    pub number: u32,
+}
+
+/// A global cursor position together with the version of the layout it refers to
+#[derive(Debug)]
+pub struct GlobalPosition {
+    pub position: MousePosition,
+    /// Missing for peers that don't share their layout
+    pub layout_version: Option<u64>,
+    /// See `DisplayManager::displays_hash`
+    pub displays_hash: Option<u64>,
+    /// The peer that sent the position, not serialized
+    pub src: Option<SocketAddrV4>,
+}
+
 pub trait Event
 where
     Self: Send + Sync,
 {
     fn serialize(&self) -> Result<String>;
 }
-impl Event for MousePosition {
+impl Event for GlobalPosition {
     fn serialize(&self) -> Result<String> {
-        Ok(format!("M{}|{}", self.x, self.y))
+        match (self.layout_version, self.displays_hash) {
+            (Some(v), Some(h)) => Ok(format!(
+                "M{}|{}|{}|{}",
+                self.position.x, self.position.y, v, h
+            )),
+            (Some(v), None) => Ok(format!("M{}|{}|{}", self.position.x, self.position.y, v)),
+            _ => Ok(format!("M{}|{}", self.position.x, self.position.y)),
+        }
+    }
+}
+
+impl Event for Identify {
+    fn serialize(&self) -> Result<String> {
+        Ok(format!("I{}", self.number))
+    }
+}
+
+struct IdentifyParser {}
+
+impl IdentifyParser {
+    fn parse(&self, text: String) -> Result<Identify> {
+        match text.parse::<u32>() {
+            Ok(number) => Ok(Identify { number }),
+            Err(e) => Err(ProtocolError::ParserError("Identify", e.to_string())),
+        }
+    }
+
+    fn get_prefix(&self) -> &'static str {
+        "I"
+    }
+}
+
+impl Event for Layout {
+    fn serialize(&self) -> Result<String> {
+        Ok(format!("L{}", serde_json::to_string(self)?))
+    }
+}
+
+struct LayoutParser {}
+
+impl LayoutParser {
+    fn parse(&self, text: String) -> Result<Layout> {
+        Ok(serde_json::from_str::<Layout>(&text)?)
+    }
+
+    fn get_prefix(&self) -> &'static str {
+        "L"
     }
 }
 
@@ -94,6 +158,12 @@ impl Event for KeyInput {
     }
 }
 
+impl Event for TextInput {
+    fn serialize(&self) -> Result<String> {
+        Ok("T".to_string() + &String::from(self))
+    }
+}
+
 struct KeyInputParser {}
 
 impl KeyInputParser {
@@ -105,10 +175,38 @@ impl KeyInputParser {
     }
 }
 
+impl Event for KeyState {
+    fn serialize(&self) -> Result<String> {
+        Ok("H".to_string() + &String::from(self))
+    }
+}
+
+struct KeyStateParser {}
+
+impl KeyStateParser {
+    fn parse(&self, text: String) -> Result<KeyState> {
+        KeyState::try_from(text)
+    }
+    fn get_prefix(&self) -> &'static str {
+        "H"
+    }
+}
+
+struct TextInputParser {}
+
+impl TextInputParser {
+    fn parse(&self, text: String) -> Result<TextInput> {
+        TextInput::try_from(text)
+    }
+    fn get_prefix(&self) -> &'static str {
+        "T"
+    }
+}
+
 struct MouseMoveParser {}
 
 impl MouseMoveParser {
-    fn parse(&self, text: String) -> Result<MousePosition> {
+    fn parse(&self, text: String, src: SocketAddrV4) -> Result<GlobalPosition> {
         let mut split = text.split("|");
         let x: i32;
         let y: i32;
@@ -133,7 +231,33 @@ impl MouseMoveParser {
             }
         };
 
-        Ok(MousePosition { x, y })
+        let layout_version = match split.next().map(|v| v.parse::<u64>()) {
+            Some(Ok(v)) => Some(v),
+            Some(Err(_)) => {
+                return Err(ProtocolError::ParserError(
+                    "MouseMove",
+                    String::from("Layout version parsing"),
+                ));
+            }
+            None => None,
+        };
+        let displays_hash = match split.next().map(|v| v.parse::<u64>()) {
+            Some(Ok(v)) => Some(v),
+            Some(Err(_)) => {
+                return Err(ProtocolError::ParserError(
+                    "MouseMove",
+                    String::from("Displays hash parsing"),
+                ));
+            }
+            None => None,
+        };
+
+        Ok(GlobalPosition {
+            position: MousePosition { x, y },
+            layout_version,
+            displays_hash,
+            src: Some(src),
+        })
     }
 
     fn get_prefix(&self) -> &'static str {
@@ -234,10 +358,14 @@ impl EventHandler {
 }
 
 pub enum Events {
-    MouseMovement(MousePosition),
+    MouseMovement(GlobalPosition),
     ClientDisplays(ClientDisplays),
     RequestDisplays(RequestDisplays),
     KeyInput(KeyInput),
+    TextInput(TextInput),
+    KeyState(KeyState),
+    Layout(Layout),
+    Identify(Identify),
 }
 
 pub struct MainParser {
@@ -245,6 +373,10 @@ pub struct MainParser {
     client_displays_parser: ClientDisplayParser,
     request_displays_parser: RequestDisplaysParser,
     key_input_parser: KeyInputParser,
+    text_input_parser: TextInputParser,
+    key_state_parser: KeyStateParser,
+    layout_parser: LayoutParser,
+    identify_parser: IdentifyParser,
 }
 
 impl MainParser {
@@ -254,6 +386,10 @@ impl MainParser {
             client_displays_parser: ClientDisplayParser {},
             request_displays_parser: RequestDisplaysParser {},
             key_input_parser: KeyInputParser {},
+            text_input_parser: TextInputParser {},
+            key_state_parser: KeyStateParser {},
+            layout_parser: LayoutParser {},
+            identify_parser: IdentifyParser {},
         }
     }
 
@@ -261,7 +397,7 @@ impl MainParser {
         return if text.starts_with(self.mouse_movement_parser.get_prefix()) {
             self.prepare_text(self.mouse_movement_parser.get_prefix(), &mut text);
             Ok(Events::MouseMovement(
-                self.mouse_movement_parser.parse(text)?,
+                self.mouse_movement_parser.parse(text, src)?,
             ))
         } else if text.starts_with(self.client_displays_parser.get_prefix()) {
             self.prepare_text(self.client_displays_parser.get_prefix(), &mut text);
@@ -276,6 +412,18 @@ impl MainParser {
         } else if text.starts_with(self.key_input_parser.get_prefix()) {
             self.prepare_text(self.key_input_parser.get_prefix(), &mut text);
             Ok(Events::KeyInput(self.key_input_parser.parse(text)?))
+        } else if text.starts_with(self.text_input_parser.get_prefix()) {
+            self.prepare_text(self.text_input_parser.get_prefix(), &mut text);
+            Ok(Events::TextInput(self.text_input_parser.parse(text)?))
+        } else if text.starts_with(self.key_state_parser.get_prefix()) {
+            self.prepare_text(self.key_state_parser.get_prefix(), &mut text);
+            Ok(Events::KeyState(self.key_state_parser.parse(text)?))
+        } else if text.starts_with(self.layout_parser.get_prefix()) {
+            self.prepare_text(self.layout_parser.get_prefix(), &mut text);
+            Ok(Events::Layout(self.layout_parser.parse(text)?))
+        } else if text.starts_with(self.identify_parser.get_prefix()) {
+            self.prepare_text(self.identify_parser.get_prefix(), &mut text);
+            Ok(Events::Identify(self.identify_parser.parse(text)?))
         } else {
             Err(ProtocolError::ParseError)
         };
@@ -287,3 +435,44 @@ impl MainParser {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn src() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 31726)
+    }
+
+    #[test]
+    fn positions_carry_the_layout_version_and_displays_hash() {
+        let position = GlobalPosition {
+            position: MousePosition { x: -20, y: 300 },
+            layout_version: Some(4),
+            displays_hash: Some(u64::MAX),
+            src: None,
+        };
+        let text = position.serialize().unwrap();
+        let parsed = match MainParser::new().parse(text, src()).unwrap() {
+            Events::MouseMovement(v) => v,
+            _ => panic!("not parsed as a position"),
+        };
+        assert_eq!((parsed.position.x, parsed.position.y), (-20, 300));
+        assert_eq!(parsed.layout_version, Some(4));
+        assert_eq!(parsed.displays_hash, Some(u64::MAX));
+        assert_eq!(parsed.src, Some(src()));
+    }
+
+    #[test]
+    fn positions_without_layout_are_still_read() {
+        let parsed = match MainParser::new()
+            .parse(String::from("M1|2"), src())
+            .unwrap()
+        {
+            Events::MouseMovement(v) => v,
+            _ => panic!("not parsed as a position"),
+        };
+        assert_eq!(parsed.layout_version, None);
+        assert_eq!(parsed.displays_hash, None);
+    }
+}
@@ -0,0 +1,182 @@
+use {
+    crate::{display::OsFamily, input::Key},
+    serde::{Deserialize, Serialize},
+    std::net::SocketAddrV4,
+};
+
+/// Replaces the keys in `from` with the keys in `to` while all of `from` are held. A single key in
+/// `from` remaps that key, several keys remap a chord and an empty `to` drops the keys.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemapRule {
+    /// Only apply to this peer. Matches every peer if unset.
+    #[serde(default)]
+    pub peer: Option<std::net::Ipv4Addr>,
+    /// Only apply to peers running this os. Matches every os if unset.
+    #[serde(default)]
+    pub os: Option<OsFamily>,
+    pub from: Vec<Key>,
+    #[serde(default)]
+    pub to: Vec<Key>,
+}
+
+impl RemapRule {
+    fn matches_peer(&self, addr: &SocketAddrV4, os: Option<OsFamily>) -> bool {
+        let peer_matches = match self.peer {
+            Some(v) => &v == addr.ip(),
+            None => true,
+        };
+        let os_matches = match self.os {
+            Some(v) => Some(v) == os,
+            None => true,
+        };
+        peer_matches && os_matches && !self.from.is_empty()
+    }
+}
+
+pub struct Remapper {
+    rules: Vec<RemapRule>,
+}
+
+impl Remapper {
+    pub fn new(mut rules: Vec<RemapRule>) -> Self {
+        // chords have to be resolved before the single keys they contain
+        rules.sort_by_key(|r| std::cmp::Reverse(r.from.len()));
+        Remapper { rules }
+    }
+
+    /// Maps the keys held locally to the keys that should be held on the peer
+    pub fn apply(&self, addr: &SocketAddrV4, os: Option<OsFamily>, held_keys: &[Key]) -> Vec<Key> {
+        let mut remaining = held_keys.to_vec();
+        let mut result: Vec<Key> = Vec::new();
+        for rule in self.rules.iter() {
+            if !rule.matches_peer(addr, os) || !rule.from.iter().all(|k| remaining.contains(k)) {
+                continue;
+            }
+            remaining.retain(|k| !rule.from.contains(k));
+            for key in rule.to.iter() {
+                if !result.contains(key) {
+                    result.push(key.clone());
+                }
+            }
+        }
+        for key in remaining {
+            if !result.contains(&key) {
+                result.push(key);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        device_query::Keycode::{LAlt, LControl, LMeta, LShift, Tab, A, C},
+    };
+
+    /// The json between the backticks after "e.g." in the README line about `name`
+    fn readme_example(name: &str) -> String {
+        let line = include_str!("../README.md")
+            .lines()
+            .find(|v| v.starts_with(&format!("- `{}`", name)))
+            .unwrap();
+        let example = line.split("e.g. `").nth(1).unwrap();
+        example.split('`').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn documented_example_parses() {
+        let rule: RemapRule = serde_json::from_str(&readme_example("key_remaps")).unwrap();
+        assert_eq!(rule.os, Some(OsFamily::MacOs));
+        assert_eq!(rule.from, vec![Key::KeyCode(LControl)]);
+        assert_eq!(rule.to, vec![Key::KeyCode(LMeta)]);
+    }
+
+    fn key(code: device_query::Keycode) -> Key {
+        Key::KeyCode(code)
+    }
+
+    fn rule(from: Vec<Key>, to: Vec<Key>) -> RemapRule {
+        RemapRule {
+            peer: None,
+            os: None,
+            from,
+            to,
+        }
+    }
+
+    fn peer() -> SocketAddrV4 {
+        SocketAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 2), 31726)
+    }
+
+    #[test]
+    fn single_keys_are_replaced() {
+        let remapper = Remapper::new(vec![rule(vec![key(LControl)], vec![key(LMeta)])]);
+        assert_eq!(
+            remapper.apply(&peer(), None, &[key(LControl), key(C)]),
+            vec![key(LMeta), key(C)]
+        );
+        assert_eq!(remapper.apply(&peer(), None, &[key(A)]), vec![key(A)]);
+    }
+
+    #[test]
+    fn chords_are_resolved_before_their_keys() {
+        let remapper = Remapper::new(vec![
+            rule(vec![key(LAlt)], vec![key(LMeta)]),
+            rule(vec![key(LAlt), key(Tab)], vec![key(LMeta), key(Tab)]),
+        ]);
+        assert_eq!(
+            remapper.apply(&peer(), None, &[key(LAlt), key(Tab)]),
+            vec![key(LMeta), key(Tab)]
+        );
+        //a partial chord falls back to the single key rule
+        assert_eq!(
+            remapper.apply(&peer(), None, &[key(LAlt), key(A)]),
+            vec![key(LMeta), key(A)]
+        );
+    }
+
+    #[test]
+    fn empty_to_drops_the_keys() {
+        let remapper = Remapper::new(vec![rule(vec![key(LShift), key(LMeta)], Vec::new())]);
+        assert_eq!(
+            remapper.apply(&peer(), None, &[key(LShift), key(LMeta), key(A)]),
+            vec![key(A)]
+        );
+        assert_eq!(
+            remapper.apply(&peer(), None, &[key(LShift)]),
+            vec![key(LShift)]
+        );
+    }
+
+    #[test]
+    fn rules_are_restricted_to_their_os_and_peer() {
+        let mut for_mac = rule(vec![key(LControl)], vec![key(LMeta)]);
+        for_mac.os = Some(OsFamily::MacOs);
+        let mut for_other_peer = rule(vec![key(A)], vec![key(C)]);
+        for_other_peer.peer = Some(std::net::Ipv4Addr::new(10, 0, 0, 3));
+        let remapper = Remapper::new(vec![for_mac, for_other_peer]);
+
+        let held = [key(LControl), key(A)];
+        assert_eq!(
+            remapper.apply(&peer(), Some(OsFamily::MacOs), &held),
+            vec![key(LMeta), key(A)]
+        );
+        assert_eq!(
+            remapper.apply(&peer(), Some(OsFamily::Linux), &held),
+            vec![key(LControl), key(A)]
+        );
+        //an unknown os does not match an os restricted rule
+        assert_eq!(
+            remapper.apply(&peer(), None, &held),
+            vec![key(LControl), key(A)]
+        );
+        //remapped keys come first, followed by the held keys no rule touched
+        let other = SocketAddrV4::new(std::net::Ipv4Addr::new(10, 0, 0, 3), 31726);
+        assert_eq!(
+            remapper.apply(&other, Some(OsFamily::Linux), &held),
+            vec![key(C), key(LControl)]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use device_query::Keycode;
+
+/// (device_query keycode, USB HID usage id, linux evdev code, windows set 1 scancode, macOS virtual keycode)
+type ScancodeRow = (Keycode, u16, u16, u16, u16);
+
+/// Physical key positions. Keys are transmitted as their usage id so the target's layout decides the
+/// character, just like a real usb keyboard.
+const SCANCODES: [ScancodeRow; 111] = [
+    (Keycode::A, 0x04, 30, 0x1E, 0x00),
+    (Keycode::B, 0x05, 48, 0x30, 0x0B),
+    (Keycode::C, 0x06, 46, 0x2E, 0x08),
+    (Keycode::D, 0x07, 32, 0x20, 0x02),
+    (Keycode::E, 0x08, 18, 0x12, 0x0E),
+    (Keycode::F, 0x09, 33, 0x21, 0x03),
+    (Keycode::G, 0x0A, 34, 0x22, 0x05),
+    (Keycode::H, 0x0B, 35, 0x23, 0x04),
+    (Keycode::I, 0x0C, 23, 0x17, 0x22),
+    (Keycode::J, 0x0D, 36, 0x24, 0x26),
+    (Keycode::K, 0x0E, 37, 0x25, 0x28),
+    (Keycode::L, 0x0F, 38, 0x26, 0x25),
+    (Keycode::M, 0x10, 50, 0x32, 0x2E),
+    (Keycode::N, 0x11, 49, 0x31, 0x2D),
+    (Keycode::O, 0x12, 24, 0x18, 0x1F),
+    (Keycode::P, 0x13, 25, 0x19, 0x23),
+    (Keycode::Q, 0x14, 16, 0x10, 0x0C),
+    (Keycode::R, 0x15, 19, 0x13, 0x0F),
+    (Keycode::S, 0x16, 31, 0x1F, 0x01),
+    (Keycode::T, 0x17, 20, 0x14, 0x11),
+    (Keycode::U, 0x18, 22, 0x16, 0x20),
+    (Keycode::V, 0x19, 47, 0x2F, 0x09),
+    (Keycode::W, 0x1A, 17, 0x11, 0x0D),
+    (Keycode::X, 0x1B, 45, 0x2D, 0x07),
+    (Keycode::Y, 0x1C, 21, 0x15, 0x10),
+    (Keycode::Z, 0x1D, 44, 0x2C, 0x06),
+    (Keycode::Key1, 0x1E, 2, 0x02, 0x12),
+    (Keycode::Key2, 0x1F, 3, 0x03, 0x13),
+    (Keycode::Key3, 0x20, 4, 0x04, 0x14),
+    (Keycode::Key4, 0x21, 5, 0x05, 0x15),
+    (Keycode::Key5, 0x22, 6, 0x06, 0x17),
+    (Keycode::Key6, 0x23, 7, 0x07, 0x16),
+    (Keycode::Key7, 0x24, 8, 0x08, 0x1A),
+    (Keycode::Key8, 0x25, 9, 0x09, 0x1C),
+    (Keycode::Key9, 0x26, 10, 0x0A, 0x19),
+    (Keycode::Key0, 0x27, 11, 0x0B, 0x1D),
+    (Keycode::Enter, 0x28, 28, 0x1C, 0x24),
+    (Keycode::Escape, 0x29, 1, 0x01, 0x35),
+    (Keycode::Backspace, 0x2A, 14, 0x0E, 0x33),
+    (Keycode::Tab, 0x2B, 15, 0x0F, 0x30),
+    (Keycode::Space, 0x2C, 57, 0x39, 0x31),
+    (Keycode::Minus, 0x2D, 12, 0x0C, 0x1B),
+    (Keycode::Equal, 0x2E, 13, 0x0D, 0x18),
+    (Keycode::LeftBracket, 0x2F, 26, 0x1A, 0x21),
+    (Keycode::RightBracket, 0x30, 27, 0x1B, 0x1E),
+    (Keycode::BackSlash, 0x31, 43, 0x2B, 0x2A),
+    (Keycode::Semicolon, 0x33, 39, 0x27, 0x29),
+    (Keycode::Apostrophe, 0x34, 40, 0x28, 0x27),
+    (Keycode::Grave, 0x35, 41, 0x29, 0x32),
+    (Keycode::Comma, 0x36, 51, 0x33, 0x2B),
+    (Keycode::Dot, 0x37, 52, 0x34, 0x2F),
+    (Keycode::Slash, 0x38, 53, 0x35, 0x2C),
+    (Keycode::CapsLock, 0x39, 58, 0x3A, 0x39),
+    (Keycode::F1, 0x3A, 59, 0x3B, 0x7A),
+    (Keycode::F2, 0x3B, 60, 0x3C, 0x78),
+    (Keycode::F3, 0x3C, 61, 0x3D, 0x63),
+    (Keycode::F4, 0x3D, 62, 0x3E, 0x76),
+    (Keycode::F5, 0x3E, 63, 0x3F, 0x60),
+    (Keycode::F6, 0x3F, 64, 0x40, 0x61),
+    (Keycode::F7, 0x40, 65, 0x41, 0x62),
+    (Keycode::F8, 0x41, 66, 0x42, 0x64),
+    (Keycode::F9, 0x42, 67, 0x43, 0x65),
+    (Keycode::F10, 0x43, 68, 0x44, 0x6D),
+    (Keycode::F11, 0x44, 87, 0x57, 0x67),
+    (Keycode::F12, 0x45, 88, 0x58, 0x6F),
+    (Keycode::Insert, 0x49, 110, 0xE052, 0x72),
+    (Keycode::Home, 0x4A, 102, 0xE047, 0x73),
+    (Keycode::PageUp, 0x4B, 104, 0xE049, 0x74),
+    (Keycode::Delete, 0x4C, 111, 0xE053, 0x75),
+    (Keycode::End, 0x4D, 107, 0xE04F, 0x77),
+    (Keycode::PageDown, 0x4E, 109, 0xE051, 0x79),
+    (Keycode::Right, 0x4F, 106, 0xE04D, 0x7C),
+    (Keycode::Left, 0x50, 105, 0xE04B, 0x7B),
+    (Keycode::Down, 0x51, 108, 0xE050, 0x7D),
+    (Keycode::Up, 0x52, 103, 0xE048, 0x7E),
+    (Keycode::NumpadDivide, 0x54, 98, 0xE035, 0x4B),
+    (Keycode::NumpadMultiply, 0x55, 55, 0x37, 0x43),
+    (Keycode::NumpadSubtract, 0x56, 74, 0x4A, 0x4E),
+    (Keycode::NumpadAdd, 0x57, 78, 0x4E, 0x45),
+    (Keycode::NumpadEnter, 0x58, 96, 0xE01C, 0x4C),
+    (Keycode::Numpad1, 0x59, 79, 0x4F, 0x53),
+    (Keycode::Numpad2, 0x5A, 80, 0x50, 0x54),
+    (Keycode::Numpad3, 0x5B, 81, 0x51, 0x55),
+    (Keycode::Numpad4, 0x5C, 75, 0x4B, 0x56),
+    (Keycode::Numpad5, 0x5D, 76, 0x4C, 0x57),
+    (Keycode::Numpad6, 0x5E, 77, 0x4D, 0x58),
+    (Keycode::Numpad7, 0x5F, 71, 0x47, 0x59),
+    (Keycode::Numpad8, 0x60, 72, 0x48, 0x5B),
+    (Keycode::Numpad9, 0x61, 73, 0x49, 0x5C),
+    (Keycode::Numpad0, 0x62, 82, 0x52, 0x52),
+    (Keycode::NumpadDecimal, 0x63, 83, 0x53, 0x41),
+    (Keycode::NumpadEquals, 0x67, 117, 0x59, 0x51),
+    (Keycode::F13, 0x68, 183, 0x64, 0x69),
+    (Keycode::F14, 0x69, 184, 0x65, 0x6B),
+    (Keycode::F15, 0x6A, 185, 0x66, 0x71),
+    (Keycode::F16, 0x6B, 186, 0x67, 0x6A),
+    (Keycode::F17, 0x6C, 187, 0x68, 0x40),
+    (Keycode::F18, 0x6D, 188, 0x69, 0x4F),
+    (Keycode::F19, 0x6E, 189, 0x6A, 0x50),
+    (Keycode::F20, 0x6F, 190, 0x6B, 0x5A),
+    (Keycode::LControl, 0xE0, 29, 0x1D, 0x3B),
+    (Keycode::LShift, 0xE1, 42, 0x2A, 0x38),
+    (Keycode::LAlt, 0xE2, 56, 0x38, 0x3A),
+    (Keycode::LMeta, 0xE3, 125, 0xE05B, 0x37),
+    (Keycode::Command, 0xE3, 125, 0xE05B, 0x37),
+    (Keycode::LOption, 0xE2, 56, 0x38, 0x3A),
+    (Keycode::RControl, 0xE4, 97, 0xE01D, 0x3E),
+    (Keycode::RShift, 0xE5, 54, 0x36, 0x3C),
+    (Keycode::RAlt, 0xE6, 100, 0xE038, 0x3D),
+    (Keycode::ROption, 0xE6, 100, 0xE038, 0x3D),
+    (Keycode::RMeta, 0xE7, 126, 0xE05C, 0x36),
+];
+
+pub fn keycode_to_usage_id(keycode: &Keycode) -> Option<u16> {
+    SCANCODES
+        .iter()
+        .find(|row| &row.0 == keycode)
+        .map(|row| row.1)
+}
+
+/// The raw keycode enigo expects on this platform for a usb usage id
+pub fn usage_id_to_raw(usage_id: u16) -> Option<u16> {
+    SCANCODES
+        .iter()
+        .find(|row| row.1 == usage_id)
+        .map(platform_code)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_code(row: &ScancodeRow) -> u16 {
+    // x11 keycodes are offset by 8 from the evdev codes
+    row.2 + 8
+}
+
+#[cfg(target_os = "windows")]
+fn platform_code(row: &ScancodeRow) -> u16 {
+    windows_raw(row.3)
+}
+
+/// enigo sends raw keys on windows as scancodes and sets the extended flag for codes above 0x7F,
+/// so the 0xE0 prefix becomes the high bit.
+#[cfg(any(target_os = "windows", test))]
+fn windows_raw(scancode: u16) -> u16 {
+    match scancode & 0xFF00 {
+        0xE000 => 0x80 | (scancode & 0x7F),
+        _ => scancode,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn platform_code(row: &ScancodeRow) -> u16 {
+    row.4
+}
+
+/// Every keycode device_query can report, in the order of `SCANCODES`
+#[cfg(test)]
+pub fn all_keycodes() -> Vec<Keycode> {
+    SCANCODES.iter().map(|row| row.0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The number of keycodes device_query 2.1 reports
+    const KEYCODE_COUNT: usize = 111;
+
+    #[test]
+    fn every_keycode_has_exactly_one_row() {
+        let keycodes: std::collections::HashSet<Keycode> = all_keycodes().into_iter().collect();
+        assert_eq!(keycodes.len(), SCANCODES.len());
+        assert_eq!(keycodes.len(), KEYCODE_COUNT);
+    }
+
+    #[test]
+    fn keycodes_translate_to_usage_ids() {
+        let cases = [
+            (Keycode::A, 0x04),
+            (Keycode::Key1, 0x1E),
+            (Keycode::Enter, 0x28),
+            (Keycode::Up, 0x52),
+            (Keycode::NumpadEnter, 0x58),
+            (Keycode::LControl, 0xE0),
+            (Keycode::LMeta, 0xE3),
+            (Keycode::Command, 0xE3),
+            (Keycode::RMeta, 0xE7),
+        ];
+        for (keycode, usage_id) in cases {
+            assert_eq!(keycode_to_usage_id(&keycode), Some(usage_id), "{}", keycode);
+        }
+    }
+
+    #[test]
+    fn every_usage_id_has_a_raw_code() {
+        for row in SCANCODES.iter() {
+            assert_eq!(
+                usage_id_to_raw(row.1),
+                Some(platform_code(row)),
+                "{}",
+                row.0
+            );
+        }
+        assert_eq!(usage_id_to_raw(0xFFFF), None);
+    }
+
+    #[test]
+    fn keys_sharing_a_usage_id_share_their_codes() {
+        for row in SCANCODES.iter() {
+            for other in SCANCODES.iter().filter(|v| v.1 == row.1) {
+                assert_eq!(
+                    (row.2, row.3, row.4),
+                    (other.2, other.3, other.4),
+                    "{}",
+                    row.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn windows_extended_scancodes_set_the_high_bit() {
+        assert_eq!(windows_raw(0x1E), 0x1E);
+        assert_eq!(windows_raw(0xE048), 0xC8);
+        assert_eq!(windows_raw(0xE05B), 0xDB);
+        for row in SCANCODES.iter() {
+            assert!(windows_raw(row.3) <= 0xFF, "{}", row.0);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use {
+    crate::{display::Edge, input::Key},
+    serde::{Deserialize, Serialize},
+    std::time::{Duration, Instant},
+};
+
+/// Conditions that have to be met before the cursor may leave through an edge. Every condition
+/// that is set has to be met.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SwitchPolicy {
+    /// Milliseconds the cursor has to rest against the edge
+    pub dwell: Option<u64>,
+    /// Milliseconds in which the edge has to be hit twice
+    pub double_tap: Option<u64>,
+    /// A key that has to be held while crossing
+    pub modifier: Option<Key>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SwitchPolicies {
+    pub left: SwitchPolicy,
+    pub right: SwitchPolicy,
+    pub top: SwitchPolicy,
+    pub bottom: SwitchPolicy,
+}
+
+impl SwitchPolicies {
+    pub fn get(&self, edge: &Edge) -> &SwitchPolicy {
+        match edge {
+            Edge::Left => &self.left,
+            Edge::Right => &self.right,
+            Edge::Top => &self.top,
+            Edge::Bottom => &self.bottom,
+        }
+    }
+}
+
+struct EdgeContact {
+    edge: Edge,
+    since: Instant,
+    /// Set once the double tap of this contact was completed
+    tapped: bool,
+}
+
+pub struct SwitchGuard {
+    policies: SwitchPolicies,
+    /// The keys held on this machine, see `set_held_keys`
+    held_keys: Vec<Key>,
+    contact: Option<EdgeContact>,
+    last_bump: Option<(Edge, Instant)>,
+}
+
+impl SwitchGuard {
+    pub fn new(policies: SwitchPolicies) -> Self {
+        SwitchGuard {
+            policies,
+            held_keys: Vec::new(),
+            contact: None,
+            last_bump: None,
+        }
+    }
+
+    pub fn set_held_keys(&mut self, keys: Vec<Key>) {
+        self.held_keys = keys;
+    }
+
+    /// Called for every movement that would leave through `edge`. Returns whether it may.
+    pub fn attempt(&mut self, edge: Edge) -> bool {
+        let now = Instant::now();
+        let new_contact = match &self.contact {
+            Some(v) => v.edge != edge,
+            None => true,
+        };
+
+        if new_contact {
+            let tapped = match (self.policies.get(&edge).double_tap, self.last_bump) {
+                (Some(window), Some((last_edge, last_time))) => {
+                    last_edge == edge
+                        && now.duration_since(last_time) <= Duration::from_millis(window)
+                }
+                _ => false,
+            };
+            self.last_bump = Some((edge, now));
+            self.contact = Some(EdgeContact {
+                edge,
+                since: now,
+                tapped,
+            });
+        }
+
+        self.ready()
+    }
+
+    /// Whether the current edge contact meets every condition of its policy
+    pub fn ready(&self) -> bool {
+        let contact = match &self.contact {
+            Some(v) => v,
+            None => return false,
+        };
+        let policy = self.policies.get(&contact.edge);
+
+        if let Some(modifier) = &policy.modifier {
+            if !self.held_keys.contains(modifier) {
+                return false;
+            }
+        }
+        if policy.double_tap.is_some() && !contact.tapped {
+            return false;
+        }
+        if let Some(dwell) = policy.dwell {
+            if contact.since.elapsed() < Duration::from_millis(dwell) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// The cursor moved without trying to leave
+    pub fn released(&mut self) {
+        self.contact = None;
+    }
+
+    pub fn switched(&mut self) {
+        self.contact = None;
+        self.last_bump = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, device_query::Keycode, std::thread::sleep};
+
+    fn guard(right: SwitchPolicy) -> SwitchGuard {
+        SwitchGuard::new(SwitchPolicies {
+            right,
+            ..SwitchPolicies::default()
+        })
+    }
+
+    #[test]
+    fn edges_without_a_policy_switch_right_away() {
+        let mut guard = guard(SwitchPolicy::default());
+        assert!(guard.attempt(Edge::Right));
+        assert!(guard.attempt(Edge::Left));
+    }
+
+    #[test]
+    fn dwell_waits_at_the_edge() {
+        let mut guard = guard(SwitchPolicy {
+            dwell: Some(30),
+            ..SwitchPolicy::default()
+        });
+        assert!(!guard.attempt(Edge::Right));
+        assert!(!guard.ready());
+        sleep(Duration::from_millis(40));
+        assert!(guard.ready());
+        assert!(guard.attempt(Edge::Right));
+
+        //leaving the edge starts the wait over
+        guard.released();
+        assert!(!guard.ready());
+        assert!(!guard.attempt(Edge::Right));
+        //the other edges have no policy
+        assert!(guard.attempt(Edge::Left));
+    }
+
+    #[test]
+    fn double_tap_needs_a_second_bump_in_time() {
+        let mut guard = guard(SwitchPolicy {
+            double_tap: Some(30),
+            ..SwitchPolicy::default()
+        });
+        assert!(!guard.attempt(Edge::Right));
+        guard.released();
+        assert!(guard.attempt(Edge::Right));
+
+        guard.switched();
+        assert!(!guard.attempt(Edge::Right));
+        guard.released();
+        sleep(Duration::from_millis(40));
+        assert!(!guard.attempt(Edge::Right));
+
+        //a bump at another edge in between does not count
+        guard.released();
+        assert!(guard.attempt(Edge::Left));
+        guard.released();
+        assert!(!guard.attempt(Edge::Right));
+    }
+
+    #[test]
+    fn modifier_has_to_be_held() {
+        let mut guard = guard(SwitchPolicy {
+            modifier: Some(Key::KeyCode(Keycode::LControl)),
+            ..SwitchPolicy::default()
+        });
+        assert!(!guard.attempt(Edge::Right));
+        guard.set_held_keys(vec![Key::KeyCode(Keycode::LShift)]);
+        assert!(!guard.ready());
+        guard.set_held_keys(vec![
+            Key::KeyCode(Keycode::LShift),
+            Key::KeyCode(Keycode::LControl),
+        ]);
+        assert!(guard.ready());
+        assert!(guard.attempt(Edge::Right));
+        guard.set_held_keys(Vec::new());
+        assert!(!guard.attempt(Edge::Right));
+    }
+
+    #[test]
+    fn every_condition_has_to_be_met() {
+        let mut guard = guard(SwitchPolicy {
+            dwell: Some(30),
+            modifier: Some(Key::KeyCode(Keycode::LControl)),
+            ..SwitchPolicy::default()
+        });
+        assert!(!guard.attempt(Edge::Right));
+        sleep(Duration::from_millis(40));
+        assert!(!guard.ready());
+        guard.set_held_keys(vec![Key::KeyCode(Keycode::LControl)]);
+        assert!(guard.ready());
+    }
+}